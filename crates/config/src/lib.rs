@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -9,12 +10,113 @@ pub struct Settings {
     pub language: String,
     pub auto_lock: bool,
     pub auto_lock_timeout: u64,
+    #[serde(default)]
+    pub auto_lock_on: LockTriggers,
+    #[serde(default)]
+    pub backup: BackupPolicy,
+    #[serde(default)]
+    pub attachments: AttachmentLimits,
+    #[serde(default)]
+    pub history: HistoryLimits,
+    /// Days after which deleted items are purged from the recycle bin; never when absent
+    #[serde(default)]
+    pub recycle_bin_purge_days: Option<u64>,
 }
+
+/// Events that lock the vault regardless of the idle timeout.
+/// Locking the screen is not one of them: it is not detected, so only the idle timeout
+/// locks the vault while the screen is locked.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockTriggers {
+    /// The system waking up from sleep, noticed as a gap in the idle timer rather than
+    /// reported by the OS
+    pub sleep: bool,
+    /// A window being minimised
+    pub minimize: bool,
+}
+
+impl Default for LockTriggers {
+    fn default() -> Self {
+        Self {
+            sleep: true,
+            minimize: false,
+        }
+    }
+}
+
+/// Which copies of the vault are kept when it is overwritten
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupPolicy {
+    pub enabled: bool,
+    /// Most recent backups kept regardless of age
+    pub keep_last: usize,
+    /// Days for which the newest backup of the day is kept
+    pub keep_daily: usize,
+    /// Weeks for which the newest backup of the week is kept
+    pub keep_weekly: usize,
+    /// Empty means the `backups` directory under the app data dir
+    pub dir: PathBuf,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            keep_last: 10,
+            keep_daily: 7,
+            keep_weekly: 4,
+            dir: PathBuf::new(),
+        }
+    }
+}
+
+/// How much binary data may be attached to entries, in bytes
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentLimits {
+    /// Largest single attachment
+    pub max_size: u64,
+    /// Largest total of all attachments in the vault
+    pub max_total_size: u64,
+}
+
+impl Default for AttachmentLimits {
+    fn default() -> Self {
+        Self {
+            max_size: 10 * 1024 * 1024,
+            max_total_size: 100 * 1024 * 1024,
+        }
+    }
+}
+
+/// How many previous versions of an entry are kept, with the KeePass defaults
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryLimits {
+    pub max_items: usize,
+    /// Approximate size in bytes of all versions of one entry
+    pub max_size: u64,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        Self {
+            max_items: 10,
+            max_size: 6 * 1024 * 1024,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Builder {
     pub kdbx_path: PathBuf,
     pub settings: Settings,
+    /// Key file of each vault that uses one, by vault path. Only the paths are kept.
+    #[serde(default)]
+    pub key_files: BTreeMap<PathBuf, PathBuf>,
 }
 
 impl Builder {
@@ -22,6 +124,20 @@ impl Builder {
         self.kdbx_path = p;
         self
     }
+
+    /// Sets or forgets the key file of the vault at `kdbx_path`
+    pub fn set_key_file(mut self, key_file: Option<PathBuf>) -> Self {
+        match key_file {
+            Some(key_file) => self.key_files.insert(self.kdbx_path.clone(), key_file),
+            None => self.key_files.remove(&self.kdbx_path),
+        };
+        self
+    }
+
+    /// Key file of the vault at `kdbx_path`, if it uses one
+    pub fn key_file(&self) -> Option<&Path> {
+        self.key_files.get(&self.kdbx_path).map(PathBuf::as_path)
+    }
 }
 
 impl Default for Builder {
@@ -33,7 +149,13 @@ impl Default for Builder {
                 language: "en".to_string(),
                 auto_lock: false,
                 auto_lock_timeout: 10,
+                auto_lock_on: LockTriggers::default(),
+                backup: BackupPolicy::default(),
+                attachments: AttachmentLimits::default(),
+                history: HistoryLimits::default(),
+                recycle_bin_purge_days: None,
             },
+            key_files: BTreeMap::new(),
         }
     }
 }
@@ -1,4 +1,13 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
+
+mod otp;
+mod secret;
+
+pub use otp::*;
+pub use secret::SecretString;
+
+use secret::Redacted;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -10,19 +19,59 @@ pub struct AppDefault {
 #[serde(rename_all = "camelCase")]
 pub struct InitRequest {
     pub kdbx_path: std::path::PathBuf,
-    pub password: String,
+    pub password: SecretString,
+    /// Key file combined with the password, none when absent
+    #[serde(default)]
+    pub key_file: Option<std::path::PathBuf>,
+    /// Write a new key file for a new vault, to `key_file` or next to the vault
+    #[serde(default)]
+    pub generate_key_file: bool,
 }
 
+/// An account as listed in the UI. Secrets are left out and fetched one at a time on demand.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
-pub struct Account {
+pub struct AccountSummary {
     pub id: String,
     pub title: String,
     pub username: String,
-    pub password: String,
     pub url: String,
+    /// Empty when the notes are protected; see `notes_protected`
     pub notes: String,
-    pub totp: Option<String>,
+    pub notes_protected: bool,
+    pub has_password: bool,
+    /// Kind of one-time password configured, if any
+    pub otp_type: Option<OtpType>,
+    pub hotp_counter: Option<u64>,
+    pub tags: Vec<String>,
+    /// Values of protected fields are left empty
+    pub custom_fields: Vec<CustomField>,
+    /// Absent when the account has no recovery codes
+    pub recovery_codes: Option<RecoveryCodeCount>,
+}
+
+/// A KDBX string field beyond the standard ones
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomField {
+    pub key: String,
+    pub value: String,
+    /// Stored in memory-protected form and hidden in listings
+    #[serde(default)]
+    pub protected: bool,
+}
+
+impl fmt::Debug for CustomField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("CustomField");
+        debug.field("key", &self.key);
+        if self.protected {
+            debug.field("value", &Redacted);
+        } else {
+            debug.field("value", &self.value);
+        }
+        debug.field("protected", &self.protected).finish()
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -30,10 +79,21 @@ pub struct Account {
 pub struct CreateAccountRequest {
     pub title: String,
     pub username: String,
-    pub password: String,
+    pub password: SecretString,
     pub url: String,
     pub notes: String,
-    pub totp: Option<String>,
+    #[serde(default)]
+    pub notes_protected: bool,
+    pub totp: Option<SecretString>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub custom_fields: Vec<CustomField>,
+    /// One-time recovery codes given by the service, all unused
+    #[serde(default)]
+    pub recovery_codes: Vec<SecretString>,
+    /// Group to add the account to, the root group when absent
+    pub group_id: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -42,8 +102,328 @@ pub struct UpdateAccountRequest {
     pub id: String,
     pub title: String,
     pub username: String,
-    pub password: String,
+    /// New password, the current one is kept when absent
+    pub password: Option<SecretString>,
     pub url: String,
+    /// An empty value keeps notes that are already protected
     pub notes: String,
-    pub totp: Option<String>,
+    /// Whether the notes are protected, unchanged when absent
+    pub notes_protected: Option<bool>,
+    /// New OTP secret or URI, kept when absent and removed when empty
+    pub totp: Option<SecretString>,
+    /// Replaces the tags, kept when absent
+    pub tags: Option<Vec<String>>,
+    /// Replaces the custom fields; all are kept when absent. A protected field sent
+    /// with an empty value keeps the value it already has.
+    pub custom_fields: Option<Vec<CustomField>>,
+    /// Replaces the recovery codes, keeping the used time of codes already stored.
+    /// Kept when absent and removed when empty.
+    pub recovery_codes: Option<Vec<SecretString>>,
+    /// Group to move the account to, left where it is when absent
+    pub group_id: Option<String>,
+}
+
+/// A one-time 2FA recovery code
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCode {
+    pub code: SecretString,
+    /// Unix time the code was used at
+    pub used_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodeCount {
+    pub total: usize,
+    pub remaining: usize,
+}
+
+/// Order of search results
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum SearchSort {
+    Relevance,
+    Title,
+    Created,
+    Modified,
+}
+
+/// An account query: free text plus `tag:`, `group:`, `has:totp`, `expired:` and `weak:` filters
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchRequest {
+    #[serde(default)]
+    pub query: String,
+    /// Relevance when the query has free text, otherwise title
+    pub sort: Option<SearchSort>,
+    #[serde(default)]
+    pub descending: bool,
+    #[serde(default)]
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+/// One page of search results
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    /// Matches across all pages
+    pub total: usize,
+    pub accounts: Vec<AccountSummary>,
+}
+
+/// A tag and how many entries carry it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TagSummary {
+    pub name: String,
+    pub count: usize,
+}
+
+/// A group and its subgroups, as shown in the sidebar
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupSummary {
+    pub id: String,
+    pub name: String,
+    /// Built-in KeePass icon index
+    pub icon_id: Option<usize>,
+    pub custom_icon_id: Option<String>,
+    /// Absent for groups directly under the root group
+    pub parent_id: Option<String>,
+    /// Entries directly in this group
+    pub entry_count: usize,
+    /// Entries in this group and all of its subgroups
+    pub total_entry_count: usize,
+    pub expanded: bool,
+    /// Whether this is the recycle bin group
+    pub recycle_bin: bool,
+    pub children: Vec<GroupSummary>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateGroupRequest {
+    pub name: String,
+    /// Parent group, the root group when absent
+    pub parent_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OtpCode {
+    pub code: String,
+    pub otp_type: OtpType,
+    pub period: Option<u64>,
+    pub remaining: Option<u64>,
+    pub counter: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsupportedEntry {
+    pub name: String,
+    pub reason: String,
+}
+
+/// Authenticator app whose export is being imported
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportSource {
+    GoogleAuthenticator,
+    Aegis,
+    TwoFas,
+    AndOtp,
+    FreeOtpPlus,
+}
+
+/// What an import would do, computed without touching the database
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportPreview {
+    /// Entries that would be added
+    pub count: usize,
+    /// Entries whose OTP secret is already in the vault
+    pub conflicts: Vec<String>,
+    pub unsupported: Vec<UnsupportedEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub imported: usize,
+    pub duplicates: Vec<String>,
+    pub unsupported: Vec<UnsupportedEntry>,
+}
+
+/// Plaintext description stored inside an encrypted backup archive
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupMetadata {
+    pub format_version: u16,
+    /// Contents of `version.txt` when the backup was taken
+    pub app_version: String,
+    pub created_at: u64,
+    pub entry_count: usize,
+    pub group_count: usize,
+    /// Hex SHA-256 of the embedded KDBX payload
+    pub checksum: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum BackupImportMode {
+    /// Overwrite the open vault with the backup, keeping its master key.
+    /// The current file is copied into the automatic backups first, even when they are turned off.
+    Replace,
+    /// Merge the backup into the open vault
+    Merge,
+    /// Report what a merge would do without writing anything
+    Preview,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupImportReport {
+    pub metadata: BackupMetadata,
+    /// Entries only present in the backup
+    pub added: Vec<String>,
+    /// Entries the backup holds a newer version of
+    pub changed: Vec<String>,
+    /// Entries edited more recently in the vault than in the backup
+    pub conflicts: Vec<String>,
+}
+
+/// Changes picked up from a vault file that was modified outside the app
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalMergeReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub removed: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// A copy of the vault taken automatically before it was overwritten
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalBackup {
+    pub name: String,
+    pub created_at: u64,
+    pub size: u64,
+}
+
+/// Secret access recorded in the audit trail
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditAction {
+    RevealPassword,
+    CopyPassword,
+    RevealField,
+    ExportAttachment,
+    RevealRecoveryCodes,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    pub timestamp: u64,
+    pub action: AuditAction,
+    pub account_id: String,
+    /// Current title of the account, absent once it has been deleted for good
+    pub title: Option<String>,
+    /// Field revealed by `RevealField`, or attachment exported by `ExportAttachment`
+    #[serde(default)]
+    pub field: Option<String>,
+}
+
+/// A file attached to an entry
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachmentSummary {
+    pub name: String,
+    pub size: u64,
+}
+
+/// Something about an account that needs attention
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum HealthIssue {
+    WeakPassword,
+    Expired,
+    #[serde(rename_all = "camelCase")]
+    LowRecoveryCodes {
+        remaining: usize,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthWarning {
+    pub account_id: String,
+    pub title: String,
+    pub issue: HealthIssue,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthReport {
+    /// Number of accounts checked
+    pub accounts: usize,
+    pub warnings: Vec<HealthWarning>,
+}
+
+/// A previous version of an entry, kept in its history
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryVersion {
+    /// Position in the history, 0 being the most recent previous version
+    pub index: usize,
+    /// Unix time the version was last modified at
+    pub modified_at: Option<u64>,
+    pub title: String,
+}
+
+/// A field that differs between two versions of an entry
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+    pub field: String,
+    /// Absent when the field was added; empty for protected fields
+    pub old: Option<String>,
+    /// Absent when the field was removed; empty for protected fields
+    pub new: Option<String>,
+    pub protected: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum RecycledKind {
+    Entry,
+    Group,
+}
+
+/// An entry or group in the recycle bin
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecycledItem {
+    pub id: String,
+    /// Title of an entry or name of a group
+    pub name: String,
+    pub kind: RecycledKind,
+    /// Unix time it was moved to the recycle bin
+    pub deleted_at: Option<u64>,
+    /// Group it is restored to; absent for the root group
+    pub original_group_id: Option<String>,
+}
+
+/// Why the vault was locked, sent with the `locked` event
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum LockReason {
+    Manual,
+    Idle,
+    Sleep,
+    Minimized,
 }
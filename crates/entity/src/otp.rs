@@ -0,0 +1,375 @@
+use crate::secret::Redacted;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use url::Url;
+use xpassword_error::CommonError;
+
+pub const DEFAULT_OTP_DIGITS: u32 = 6;
+pub const DEFAULT_OTP_PERIOD: u64 = 30;
+pub const STEAM_OTP_DIGITS: u32 = 5;
+const MIN_OTP_DIGITS: u32 = 6;
+const MAX_OTP_DIGITS: u32 = 10;
+
+/// RFC 3986 unreserved characters are the only ones left as-is in labels and values
+const OTPAUTH_ENCODE_SET: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum OtpType {
+    Totp,
+    Hotp,
+    /// Steam Guard: a TOTP rendered as five characters from Steam's alphabet
+    Steam,
+}
+
+impl fmt::Display for OtpType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtpType::Totp => write!(f, "totp"),
+            OtpType::Hotp => write!(f, "hotp"),
+            OtpType::Steam => write!(f, "steam"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum OtpAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl fmt::Display for OtpAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OtpAlgorithm::Sha1 => write!(f, "SHA1"),
+            OtpAlgorithm::Sha256 => write!(f, "SHA256"),
+            OtpAlgorithm::Sha512 => write!(f, "SHA512"),
+        }
+    }
+}
+
+impl FromStr for OtpAlgorithm {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().replace('-', "").as_str() {
+            "SHA1" => Ok(OtpAlgorithm::Sha1),
+            "SHA256" => Ok(OtpAlgorithm::Sha256),
+            "SHA512" => Ok(OtpAlgorithm::Sha512),
+            _ => Err(CommonError::InvalidOtp(format!(
+                "unsupported algorithm: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// Typed form of an `otpauth://` URI (or a bare base32 TOTP secret)
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OtpParams {
+    pub otp_type: OtpType,
+    pub issuer: Option<String>,
+    pub label: String,
+    /// Normalised base32: uppercase, no padding or separators
+    pub secret: String,
+    pub algorithm: OtpAlgorithm,
+    pub digits: u32,
+    /// Time step in seconds, only used by TOTP
+    pub period: u64,
+    /// Initial moving factor, only used by HOTP
+    pub counter: u64,
+    pub image: Option<String>,
+}
+
+impl OtpParams {
+    pub fn totp(secret: &str) -> Result<Self, CommonError> {
+        let params = Self {
+            otp_type: OtpType::Totp,
+            issuer: None,
+            label: String::new(),
+            secret: normalize_secret(secret),
+            algorithm: OtpAlgorithm::Sha1,
+            digits: DEFAULT_OTP_DIGITS,
+            period: DEFAULT_OTP_PERIOD,
+            counter: 0,
+            image: None,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Checks the secret decodes and that digits/period are in range
+    pub fn validate(&self) -> Result<(), CommonError> {
+        self.secret_bytes()?;
+
+        if self.otp_type != OtpType::Steam
+            && !(MIN_OTP_DIGITS..=MAX_OTP_DIGITS).contains(&self.digits)
+        {
+            return Err(CommonError::InvalidOtp(format!(
+                "digits must be between {} and {}",
+                MIN_OTP_DIGITS, MAX_OTP_DIGITS
+            )));
+        }
+        if self.otp_type != OtpType::Hotp && self.period == 0 {
+            return Err(CommonError::InvalidOtp(
+                "period must be greater than zero".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn secret_bytes(&self) -> Result<Vec<u8>, CommonError> {
+        base32::decode(base32::Alphabet::Rfc4648 { padding: false }, &self.secret)
+            .filter(|s| !s.is_empty())
+            .ok_or(CommonError::InvalidOtp(
+                "secret is not valid base32".to_string(),
+            ))
+    }
+
+    fn from_uri(uri: &str) -> Result<Self, CommonError> {
+        let url = Url::parse(uri).map_err(|e| CommonError::InvalidOtp(e.to_string()))?;
+
+        if url.scheme() != "otpauth" {
+            return Err(CommonError::InvalidOtp(format!(
+                "unsupported scheme: {}",
+                url.scheme()
+            )));
+        }
+
+        let mut otp_type = match url.host_str().map(|t| t.to_ascii_lowercase()).as_deref() {
+            Some("totp") => OtpType::Totp,
+            Some("hotp") => OtpType::Hotp,
+            Some("steam") => OtpType::Steam,
+            Some(other) => {
+                return Err(CommonError::InvalidOtp(format!(
+                    "unsupported type: {}",
+                    other
+                )))
+            }
+            None => return Err(CommonError::InvalidOtp("missing type".to_string())),
+        };
+
+        let path = percent_decode_str(url.path().trim_start_matches('/'))
+            .decode_utf8()
+            .map_err(|_| CommonError::InvalidOtp("label is not valid UTF-8".to_string()))?;
+        let (label_issuer, label) = match path.split_once(':') {
+            Some((issuer, label)) => (Some(issuer.trim().to_string()), label.trim().to_string()),
+            None => (None, path.trim().to_string()),
+        };
+
+        let mut params = Self {
+            otp_type,
+            issuer: label_issuer.filter(|i| !i.is_empty()),
+            label,
+            secret: String::new(),
+            algorithm: OtpAlgorithm::Sha1,
+            digits: DEFAULT_OTP_DIGITS,
+            period: DEFAULT_OTP_PERIOD,
+            counter: 0,
+            image: None,
+        };
+        let mut has_counter = false;
+
+        for (key, value) in url.query_pairs() {
+            match key.to_ascii_lowercase().as_str() {
+                "secret" => params.secret = normalize_secret(&value),
+                // The issuer parameter wins over the label prefix, as in Google's key URI format
+                "issuer" if !value.trim().is_empty() => {
+                    params.issuer = Some(value.trim().to_string())
+                }
+                "algorithm" => params.algorithm = value.parse()?,
+                "digits" => params.digits = parse_number(&key, &value)?,
+                "period" => params.period = parse_number(&key, &value)?,
+                "counter" => {
+                    params.counter = parse_number(&key, &value)?;
+                    has_counter = true;
+                }
+                "image" if !value.is_empty() => params.image = Some(value.to_string()),
+                // KeePassXC marks Steam Guard entries on an otherwise plain totp URI
+                "encoder" if value.eq_ignore_ascii_case("steam") => otp_type = OtpType::Steam,
+                _ => {}
+            }
+        }
+
+        if params.secret.is_empty() {
+            return Err(CommonError::InvalidOtp("missing secret".to_string()));
+        }
+        if otp_type == OtpType::Steam {
+            params.otp_type = otp_type;
+            params.digits = STEAM_OTP_DIGITS;
+        }
+        if otp_type == OtpType::Hotp && !has_counter {
+            return Err(CommonError::InvalidOtp(
+                "hotp uri is missing the counter".to_string(),
+            ));
+        }
+
+        params.validate()?;
+        Ok(params)
+    }
+}
+
+impl fmt::Debug for OtpParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OtpParams")
+            .field("otp_type", &self.otp_type)
+            .field("issuer", &self.issuer)
+            .field("label", &self.label)
+            .field("secret", &Redacted)
+            .field("algorithm", &self.algorithm)
+            .field("digits", &self.digits)
+            .field("period", &self.period)
+            .field("counter", &self.counter)
+            .field("image", &self.image)
+            .finish()
+    }
+}
+
+impl FromStr for OtpParams {
+    type Err = CommonError;
+
+    /// Parses an `otpauth://totp|hotp/...` URI, or a bare base32 secret as a default TOTP
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.to_ascii_lowercase().starts_with("otpauth://") {
+            Self::from_uri(s)
+        } else {
+            Self::totp(s)
+        }
+    }
+}
+
+impl fmt::Display for OtpParams {
+    /// Writes the canonical `otpauth://` URI
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encode = |s: &str| utf8_percent_encode(s, OTPAUTH_ENCODE_SET).to_string();
+
+        let kind = match self.otp_type {
+            OtpType::Hotp => OtpType::Hotp,
+            OtpType::Totp | OtpType::Steam => OtpType::Totp,
+        };
+        write!(f, "otpauth://{}/", kind)?;
+        if let Some(issuer) = &self.issuer {
+            write!(f, "{}:", encode(issuer))?;
+        }
+        write!(f, "{}?secret={}", encode(&self.label), self.secret)?;
+        if let Some(issuer) = &self.issuer {
+            write!(f, "&issuer={}", encode(issuer))?;
+        }
+        write!(f, "&algorithm={}&digits={}", self.algorithm, self.digits)?;
+        match self.otp_type {
+            OtpType::Totp => write!(f, "&period={}", self.period)?,
+            OtpType::Hotp => write!(f, "&counter={}", self.counter)?,
+            OtpType::Steam => write!(f, "&period={}&encoder=steam", self.period)?,
+        }
+        if let Some(image) = &self.image {
+            write!(f, "&image={}", encode(image))?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_number<T: FromStr>(key: &str, value: &str) -> Result<T, CommonError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| CommonError::InvalidOtp(format!("invalid {}: {}", key, value)))
+}
+
+/// Uppercases a base32 secret and strips spaces, dashes and padding
+pub fn normalize_secret(secret: &str) -> String {
+    secret
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && *c != '=')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_full_uri() {
+        let params: OtpParams = "otpauth://totp/ACME%20Co:john@example.com?secret=jbsw-y3dp-ehpk-3pxp&issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60"
+            .parse()
+            .unwrap();
+        assert_eq!(params.otp_type, OtpType::Totp);
+        assert_eq!(params.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(params.label, "john@example.com");
+        assert_eq!(params.secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(params.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!(params.digits, 8);
+        assert_eq!(params.period, 60);
+    }
+
+    #[test]
+    fn issuer_parameter_wins_over_label_prefix() {
+        let params: OtpParams = "otpauth://totp/Old:alice?secret=JBSWY3DPEHPK3PXP&issuer=New"
+            .parse()
+            .unwrap();
+        assert_eq!(params.issuer.as_deref(), Some("New"));
+        assert_eq!(params.label, "alice");
+    }
+
+    #[test]
+    fn bare_secret_is_a_default_totp() {
+        let params: OtpParams = " jbswy3dpehpk3pxp== ".parse().unwrap();
+        assert_eq!(params, OtpParams::totp("JBSWY3DPEHPK3PXP").unwrap());
+        assert_eq!(params.digits, DEFAULT_OTP_DIGITS);
+        assert_eq!(params.period, DEFAULT_OTP_PERIOD);
+    }
+
+    #[test]
+    fn serializes_and_parses_back() {
+        for uri in [
+            "otpauth://totp/ACME%20Co:john%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=ACME%20Co&algorithm=SHA1&digits=6&period=30",
+            "otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=SHA512&digits=8&counter=42",
+            "otpauth://totp/Steam:bob?secret=JBSWY3DPEHPK3PXP&issuer=Steam&algorithm=SHA1&digits=5&period=30&encoder=steam",
+        ] {
+            let params: OtpParams = uri.parse().unwrap();
+            assert_eq!(params.to_string(), uri);
+            assert_eq!(params.to_string().parse::<OtpParams>().unwrap(), params);
+        }
+    }
+
+    #[test]
+    fn steam_encoder_forces_five_digits() {
+        let params: OtpParams = "otpauth://totp/Steam:bob?secret=JBSWY3DPEHPK3PXP&encoder=steam"
+            .parse()
+            .unwrap();
+        assert_eq!(params.otp_type, OtpType::Steam);
+        assert_eq!(params.digits, STEAM_OTP_DIGITS);
+    }
+
+    #[test]
+    fn rejects_invalid_uris() {
+        for uri in [
+            "https://totp/alice?secret=JBSWY3DPEHPK3PXP",
+            "otpauth://motp/alice?secret=JBSWY3DPEHPK3PXP",
+            "otpauth://totp/alice",
+            "otpauth://totp/alice?secret=not-base32!",
+            "otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP",
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&digits=4",
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&period=0",
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=MD5",
+        ] {
+            assert!(
+                matches!(uri.parse::<OtpParams>(), Err(CommonError::InvalidOtp(_))),
+                "{}",
+                uri
+            );
+        }
+    }
+}
@@ -24,6 +24,10 @@ pub enum CommonError {
     TokenExpired,
     #[error("Mutex lock failed")]
     MutexLockFailed,
+    #[error("Invalid OTP: {0}")]
+    InvalidOtp(String),
+    #[error("Invalid key file: {0}")]
+    InvalidKeyFile(String),
 }
 
 impl From<CommonError> for String {
@@ -52,6 +56,8 @@ impl CommonError {
             CommonError::RequestError(_) => "RequestError",
             CommonError::TokenExpired => "TokenExpired",
             CommonError::MutexLockFailed => "MutexLockFailed",
+            CommonError::InvalidOtp(_) => "InvalidOtp",
+            CommonError::InvalidKeyFile(_) => "InvalidKeyFile",
         }
     }
 }
@@ -0,0 +1,311 @@
+use crate::{decode, encode, write_atomic};
+use chrono::{Datelike, Local, NaiveDateTime};
+use keepass::DatabaseKey;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use xpassword_config::BackupPolicy;
+use xpassword_entity::LocalBackup;
+use xpassword_error::CommonError;
+
+/// Local time with milliseconds, so saves in quick succession get distinct names
+const TIMESTAMP_FORMAT: &str = "%Y%m%d%H%M%S%3f";
+
+/// Timestamped copies of a vault file, taken before it is overwritten
+/// and pruned according to a [`BackupPolicy`]
+#[derive(Clone, Debug)]
+pub struct BackupRotation {
+    dir: PathBuf,
+    policy: BackupPolicy,
+}
+
+struct Backup {
+    path: PathBuf,
+    taken_at: NaiveDateTime,
+}
+
+impl BackupRotation {
+    pub fn new(dir: PathBuf, policy: BackupPolicy) -> Self {
+        Self { dir, policy }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Copies `vault` into the backup directory, then drops the copies the policy no longer keeps
+    pub fn rotate(&self, vault: &Path) -> Result<(), CommonError> {
+        if !self.policy.enabled {
+            return Ok(());
+        }
+        self.snapshot(vault)
+    }
+
+    /// Like [`BackupRotation::rotate`], but also when the policy is disabled,
+    /// for overwrites that must always leave a copy behind
+    pub fn snapshot(&self, vault: &Path) -> Result<(), CommonError> {
+        if !vault.exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir).map_err(|e| {
+            CommonError::UnexpectedError(anyhow::anyhow!(
+                "Failed to create backups directory: {}",
+                e
+            ))
+        })?;
+
+        let name = format!(
+            "{}-{}.kdbx",
+            stem(vault),
+            Local::now().format(TIMESTAMP_FORMAT)
+        );
+        fs::copy(vault, self.dir.join(name)).map_err(|e| {
+            CommonError::UnexpectedError(anyhow::anyhow!("Failed to back up KDBX: {}", e))
+        })?;
+
+        self.prune(vault)
+    }
+
+    /// Backups of `vault`, newest first
+    pub fn list(&self, vault: &Path) -> Result<Vec<LocalBackup>, CommonError> {
+        Ok(self
+            .backups(vault)?
+            .into_iter()
+            .map(|backup| LocalBackup {
+                name: file_name(&backup.path),
+                created_at: backup
+                    .taken_at
+                    .and_local_timezone(Local)
+                    .earliest()
+                    .map(|t| t.timestamp() as u64)
+                    .unwrap_or_default(),
+                size: fs::metadata(&backup.path)
+                    .map(|m| m.len())
+                    .unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// Path of the backup of `vault` called `name`.
+    /// Anything that is not one of its backups, including other paths, is rejected.
+    pub fn resolve(&self, vault: &Path, name: &str) -> Result<PathBuf, CommonError> {
+        self.backups(vault)?
+            .into_iter()
+            .find(|backup| file_name(&backup.path) == name)
+            .map(|backup| backup.path)
+            .ok_or(CommonError::RequestError(format!(
+                "no backup named {}",
+                name
+            )))
+    }
+
+    /// Re-encrypts the backups of `vault` that open with `old` so they open with `new`,
+    /// keeping their names. Backups made under yet another key are left as they are.
+    pub fn rekey(
+        &self,
+        vault: &Path,
+        old: &DatabaseKey,
+        new: &DatabaseKey,
+    ) -> Result<(), CommonError> {
+        for backup in self.backups(vault)? {
+            let data = fs::read(&backup.path).map_err(|e| {
+                CommonError::UnexpectedError(anyhow::anyhow!("Failed to read backup: {}", e))
+            })?;
+            let Ok(db) = decode(&data, old) else {
+                continue;
+            };
+            write_atomic(&backup.path, &encode(&db, new)?)?;
+        }
+        Ok(())
+    }
+
+    /// Keeps the `keep_last` newest backups plus the newest one of each of the
+    /// `keep_daily` most recent days and `keep_weekly` most recent weeks that have any
+    fn prune(&self, vault: &Path) -> Result<(), CommonError> {
+        let backups = self.backups(vault)?;
+        let mut days = HashSet::new();
+        let mut weeks = HashSet::new();
+
+        for (i, backup) in backups.iter().enumerate() {
+            let date = backup.taken_at.date();
+            let week = date.iso_week();
+            let newest_of_day = days.len() < self.policy.keep_daily && days.insert(date);
+            let newest_of_week =
+                weeks.len() < self.policy.keep_weekly && weeks.insert((week.year(), week.week()));
+
+            if i < self.policy.keep_last || newest_of_day || newest_of_week {
+                continue;
+            }
+            fs::remove_file(&backup.path).map_err(|e| {
+                CommonError::UnexpectedError(anyhow::anyhow!("Failed to prune backup: {}", e))
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Files in the backup directory named after `vault`, newest first
+    fn backups(&self, vault: &Path) -> Result<Vec<Backup>, CommonError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(CommonError::UnexpectedError(anyhow::anyhow!(
+                    "Failed to read backups directory: {}",
+                    e
+                )))
+            }
+        };
+
+        let prefix = format!("{}-", stem(vault));
+        let mut backups: Vec<Backup> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                let name = file_name(&path);
+                let timestamp = name.strip_prefix(&prefix)?.strip_suffix(".kdbx")?;
+                let taken_at = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+                Some(Backup { path, taken_at })
+            })
+            .collect();
+        backups.sort_by_key(|backup| Reverse(backup.taken_at));
+
+        Ok(backups)
+    }
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempDir;
+
+    /// Prunes backups of `vault.kdbx` taken at `stamps` (`%Y%m%d%H%M%S`) under `policy`,
+    /// returning the stamps left, newest first
+    fn prune(dir: &TempDir, stamps: &[&str], policy: BackupPolicy) -> Vec<String> {
+        let rotation = BackupRotation::new(dir.join("backups"), policy);
+        fs::create_dir_all(rotation.dir()).unwrap();
+        for stamp in stamps {
+            fs::write(rotation.dir().join(format!("vault-{}000.kdbx", stamp)), b"").unwrap();
+        }
+
+        let vault = dir.join("vault.kdbx");
+        rotation.prune(&vault).unwrap();
+        rotation
+            .list(&vault)
+            .unwrap()
+            .into_iter()
+            .map(|backup| backup.name[6..20].to_string())
+            .collect()
+    }
+
+    fn policy(keep_last: usize, keep_daily: usize, keep_weekly: usize) -> BackupPolicy {
+        BackupPolicy {
+            enabled: true,
+            keep_last,
+            keep_daily,
+            keep_weekly,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn keeps_the_newest_backups() {
+        let dir = TempDir::new("prune-last");
+        let stamps = [
+            "20260107180000",
+            "20260107090000",
+            "20260106120000",
+            "20260102120000",
+        ];
+
+        assert_eq!(
+            prune(&dir, &stamps, policy(2, 0, 0)),
+            ["20260107180000", "20260107090000"]
+        );
+    }
+
+    #[test]
+    fn keeps_the_newest_backup_of_each_recent_day() {
+        let dir = TempDir::new("prune-daily");
+        let stamps = [
+            "20260105180000",
+            "20260105090000",
+            "20260104120000",
+            "20260103120000",
+        ];
+
+        assert_eq!(
+            prune(&dir, &stamps, policy(0, 2, 0)),
+            ["20260105180000", "20260104120000"]
+        );
+    }
+
+    #[test]
+    fn keeps_the_newest_backup_of_each_recent_week() {
+        let dir = TempDir::new("prune-weekly");
+        // 2025-12-30 falls in the first ISO week of 2026, like 2026-01-02
+        let stamps = [
+            "20260107120000",
+            "20260105120000",
+            "20260102120000",
+            "20251230120000",
+            "20251224120000",
+            "20251217120000",
+        ];
+
+        assert_eq!(
+            prune(&dir, &stamps, policy(0, 0, 3)),
+            ["20260107120000", "20260102120000", "20251224120000"]
+        );
+    }
+
+    #[test]
+    fn keeps_what_any_rule_keeps_and_leaves_other_files_alone() {
+        let dir = TempDir::new("prune-combined");
+        fs::create_dir_all(dir.join("backups")).unwrap();
+        fs::write(dir.join("backups/other-20200101000000000.kdbx"), b"").unwrap();
+        fs::write(dir.join("backups/vault-notes.kdbx"), b"").unwrap();
+        let stamps = [
+            "20260107180000",
+            "20260107090000",
+            "20260106120000",
+            "20260102120000",
+            "20251224120000",
+        ];
+
+        assert_eq!(
+            prune(&dir, &stamps, policy(1, 2, 2)),
+            ["20260107180000", "20260106120000", "20260102120000"]
+        );
+        assert!(dir.join("backups/other-20200101000000000.kdbx").exists());
+        assert!(dir.join("backups/vault-notes.kdbx").exists());
+    }
+
+    #[test]
+    fn rotation_prunes_after_each_copy() {
+        let dir = TempDir::new("prune-rotate");
+        let vault = dir.join("vault.kdbx");
+        fs::write(&vault, b"vault").unwrap();
+        let rotation = BackupRotation::new(dir.join("backups"), policy(1, 0, 0));
+
+        for _ in 0..3 {
+            rotation.rotate(&vault).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        assert_eq!(rotation.list(&vault).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::write_atomic;
+use base64::Engine;
+use keepass::DatabaseKey;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::Path;
+use xml::reader::{EventReader, XmlEvent};
+use xpassword_error::CommonError;
+use zeroize::Zeroizing;
+
+/// Length of the key a key file stands for
+const KEY_LEN: usize = 32;
+
+/// Builds the vault key from the master password and, when given, a key file
+pub fn database_key(password: &str, key_file: Option<&Path>) -> Result<DatabaseKey, CommonError> {
+    let key = DatabaseKey::new().with_password(password);
+    let Some(path) = key_file else {
+        return Ok(key);
+    };
+    // keepass takes a 32-byte file as the key itself, so it gets the key already parsed
+    let data = read(path)?;
+    key.with_keyfile(&mut data.as_slice()).map_err(|e| {
+        CommonError::UnexpectedError(anyhow::anyhow!("Failed to read key file: {}", e))
+    })
+}
+
+/// The key a key file stands for, the way KeePass reads it: the key of a KeePass XML key
+/// file, a 32-byte file or 64 hex digits as they are, and the SHA-256 of any other file
+pub fn read(path: &Path) -> Result<Zeroizing<Vec<u8>>, CommonError> {
+    let data = Zeroizing::new(fs::read(path).map_err(|e| {
+        CommonError::InvalidKeyFile(format!("cannot read {}: {}", path.display(), e))
+    })?);
+
+    if let Some(key) = parse_xml(&data)? {
+        return Ok(key);
+    }
+    if data.len() == KEY_LEN {
+        return Ok(data);
+    }
+    if data.len() == KEY_LEN * 2 {
+        if let Ok(key) = hex::decode(data.as_slice()) {
+            return Ok(Zeroizing::new(key));
+        }
+    }
+    Ok(Zeroizing::new(Sha256::digest(data.as_slice()).to_vec()))
+}
+
+/// Writes a KeePass XML v2 key file with a new random key to `path`, which must not exist yet
+pub fn generate(path: &Path) -> Result<(), CommonError> {
+    if path.exists() {
+        return Err(CommonError::RequestError(format!(
+            "{} already exists",
+            path.display()
+        )));
+    }
+
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    getrandom::fill(key.as_mut()).map_err(|e| {
+        CommonError::UnexpectedError(anyhow::anyhow!("Failed to get randomness: {}", e))
+    })?;
+    let hash = hex::encode_upper(&Sha256::digest(key.as_slice())[..4]);
+    let digits = Zeroizing::new(hex::encode_upper(key.as_slice()));
+    let lines = Zeroizing::new(
+        digits
+            .as_bytes()
+            .chunks(32)
+            .map(|line| {
+                let groups: Vec<&str> = line
+                    .chunks(8)
+                    .map(|group| std::str::from_utf8(group).unwrap_or_default())
+                    .collect();
+                format!("\t\t\t{}\n", groups.join(" "))
+            })
+            .collect::<String>(),
+    );
+
+    let xml = Zeroizing::new(format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <KeyFile>\n\
+         \t<Meta>\n\
+         \t\t<Version>2.0</Version>\n\
+         \t</Meta>\n\
+         \t<Key>\n\
+         \t\t<Data Hash=\"{}\">\n\
+         {}\
+         \t\t</Data>\n\
+         \t</Key>\n\
+         </KeyFile>\n",
+        hash, *lines
+    ));
+    write_atomic(path, xml.as_bytes())
+}
+
+/// The key of a KeePass XML key file, version 1 (base64) or 2 (hex with a checksum).
+/// Returns `None` when `data` is not one.
+fn parse_xml(data: &[u8]) -> Result<Option<Zeroizing<Vec<u8>>>, CommonError> {
+    let mut path: Vec<String> = Vec::new();
+    let mut is_key_file = false;
+    let mut version = String::new();
+    let mut hash = None;
+    let mut text = Zeroizing::new(String::new());
+
+    for event in EventReader::new(data) {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) if is_key_file => return Err(CommonError::InvalidKeyFile(e.to_string())),
+            Err(_) => return Ok(None),
+        };
+        match event {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => {
+                if path.is_empty() {
+                    if name.local_name != "KeyFile" {
+                        return Ok(None);
+                    }
+                    is_key_file = true;
+                }
+                path.push(name.local_name);
+                if path == ["KeyFile", "Key", "Data"] {
+                    hash = attributes
+                        .into_iter()
+                        .find(|a| a.name.local_name == "Hash")
+                        .map(|a| a.value);
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                path.pop();
+            }
+            XmlEvent::Characters(value) => {
+                if path == ["KeyFile", "Meta", "Version"] {
+                    version = value.trim().to_string();
+                } else if path == ["KeyFile", "Key", "Data"] {
+                    text.push_str(&value);
+                }
+            }
+            _ => {}
+        }
+    }
+    if !is_key_file {
+        return Ok(None);
+    }
+
+    let invalid = |reason: &str| CommonError::InvalidKeyFile(reason.to_string());
+    let digits = Zeroizing::new(
+        text.chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>(),
+    );
+    let key = match version.split('.').next() {
+        Some("1") => Zeroizing::new(
+            base64::engine::general_purpose::STANDARD
+                .decode(digits.as_bytes())
+                .map_err(|_| invalid("key is not valid base64"))?,
+        ),
+        Some("2") => {
+            let key = Zeroizing::new(
+                hex::decode(digits.as_bytes()).map_err(|_| invalid("key is not valid hex"))?,
+            );
+            let expected = hash
+                .map(|hash| hex::decode(hash.trim()))
+                .transpose()
+                .map_err(|_| invalid("checksum is not valid hex"))?;
+            if expected.is_some_and(|hash| hash != Sha256::digest(key.as_slice())[..4]) {
+                return Err(invalid("checksum does not match the key"));
+            }
+            key
+        }
+        _ => return Err(invalid("unsupported key file version")),
+    };
+    if key.is_empty() {
+        return Err(invalid("key is empty"));
+    }
+    Ok(Some(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempDir;
+
+    const KEY: [u8; KEY_LEN] = *b"0123456789abcdefghijklmnopqrstuv";
+
+    fn read_file(dir: &TempDir, name: &str, data: &[u8]) -> Result<Vec<u8>, CommonError> {
+        let path = dir.join(name);
+        fs::write(&path, data).unwrap();
+        read(&path).map(|key| key.to_vec())
+    }
+
+    fn xml_v2(key: &[u8], hash: &str) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<KeyFile>\n\t<Meta>\n\t\t<Version>2.0</Version>\n\t</Meta>\n\t<Key>\n\t\t<Data Hash=\"{}\">\n\t\t\t{}\n\t\t</Data>\n\t</Key>\n</KeyFile>\n",
+            hash,
+            hex::encode_upper(key)
+        )
+    }
+
+    #[test]
+    fn reads_xml_key_files() {
+        let dir = TempDir::new("key-file-xml");
+        let v1 = format!(
+            "<KeyFile><Meta><Version>1.00</Version></Meta><Key><Data>{}</Data></Key></KeyFile>",
+            base64::engine::general_purpose::STANDARD.encode(KEY)
+        );
+        assert_eq!(read_file(&dir, "v1.key", v1.as_bytes()).unwrap(), KEY);
+
+        let hash = hex::encode_upper(&Sha256::digest(KEY)[..4]);
+        let v2 = xml_v2(&KEY, &hash);
+        assert_eq!(read_file(&dir, "v2.keyx", v2.as_bytes()).unwrap(), KEY);
+    }
+
+    #[test]
+    fn rejects_an_xml_key_file_with_a_wrong_checksum() {
+        let dir = TempDir::new("key-file-checksum");
+        let v2 = xml_v2(&KEY, "DEADBEEF");
+        assert!(matches!(
+            read_file(&dir, "v2.keyx", v2.as_bytes()),
+            Err(CommonError::InvalidKeyFile(_))
+        ));
+    }
+
+    #[test]
+    fn reads_raw_hex_and_other_files() {
+        let dir = TempDir::new("key-file-raw");
+        assert_eq!(read_file(&dir, "raw.key", &KEY).unwrap(), KEY);
+        assert_eq!(
+            read_file(&dir, "hex.key", hex::encode(KEY).as_bytes()).unwrap(),
+            KEY
+        );
+
+        let photo = b"not a key, just some file";
+        assert_eq!(
+            read_file(&dir, "photo.jpg", photo).unwrap(),
+            Sha256::digest(photo).to_vec()
+        );
+        // Malformed XML that is not a key file is hashed like any other file
+        let html = b"<html><body>";
+        assert_eq!(
+            read_file(&dir, "page.html", html).unwrap(),
+            Sha256::digest(html).to_vec()
+        );
+    }
+
+    #[test]
+    fn generates_a_readable_key_file_once() {
+        let dir = TempDir::new("key-file-generate");
+        let path = dir.join("vault.keyx");
+        generate(&path).unwrap();
+
+        let key = read(&path).unwrap();
+        assert_eq!(key.len(), KEY_LEN);
+        assert!(generate(&path).is_err());
+        assert_eq!(read(&path).unwrap(), key);
+    }
+}
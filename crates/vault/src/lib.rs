@@ -0,0 +1,704 @@
+mod backups;
+pub mod key_file;
+
+pub use backups::BackupRotation;
+
+use keepass::db::{Entry, Node, Times};
+use keepass::error::DatabaseOpenError;
+use keepass::{Database, DatabaseKey};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+use uuid::Uuid;
+use xpassword_entity::ExternalMergeReport;
+use xpassword_error::CommonError;
+
+/// Source of [`VaultStore::revision`], shared so two stores never report the same one
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+/// Called after a save had to merge in changes another program made to the file
+pub type MergeListener = Arc<dyn Fn(&ExternalMergeReport) + Send + Sync>;
+
+/// An open KDBX vault together with the key and path it is persisted with.
+///
+/// The database is only reachable mutably through [`VaultStore::update`] and
+/// [`VaultStore::replace`], which write the file before touching the in-memory
+/// copy, so the two never diverge. Changes made to the file by someone else
+/// since it was last read or written are merged in before it is overwritten.
+#[derive(Clone)]
+pub struct VaultStore {
+    db: Database,
+    key: DatabaseKey,
+    path: PathBuf,
+    fingerprint: Option<Fingerprint>,
+    on_external_merge: Option<MergeListener>,
+    backups: Option<BackupRotation>,
+    revision: u64,
+}
+
+/// What the file looked like when this store last read or wrote it
+#[derive(Clone, PartialEq)]
+struct Fingerprint {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: Vec<u8>,
+}
+
+impl Fingerprint {
+    fn of(path: &Path, data: &[u8]) -> Self {
+        Self {
+            modified: fs::metadata(path).and_then(|m| m.modified()).ok(),
+            len: data.len() as u64,
+            hash: Sha256::digest(data).to_vec(),
+        }
+    }
+}
+
+impl VaultStore {
+    /// Writes `db` to a new vault file at `path`
+    pub fn create(path: PathBuf, db: Database, key: DatabaseKey) -> Result<Self, CommonError> {
+        let mut store = Self {
+            db,
+            key,
+            path,
+            fingerprint: None,
+            on_external_merge: None,
+            backups: None,
+            revision: next_revision(),
+        };
+        store.save()?;
+        Ok(store)
+    }
+
+    /// Opens the vault at `path`, failing with `InvalidPassword` when `key` does not match
+    pub fn open(path: PathBuf, key: DatabaseKey) -> Result<Self, CommonError> {
+        if !path.exists() {
+            return Err(CommonError::KdbxNotInitialized);
+        }
+        let data = fs::read(&path).map_err(|e| {
+            CommonError::UnexpectedError(anyhow::anyhow!("Failed to open KDBX file: {}", e))
+        })?;
+        let db = decode(&data, &key).map_err(|e| match e {
+            DatabaseOpenError::Key(_) => CommonError::InvalidPassword,
+            e => CommonError::UnexpectedError(anyhow::anyhow!("Failed to read KDBX: {}", e)),
+        })?;
+
+        Ok(Self {
+            db,
+            key,
+            fingerprint: Some(Fingerprint::of(&path, &data)),
+            path,
+            on_external_merge: None,
+            backups: None,
+            revision: next_revision(),
+        })
+    }
+
+    /// Registers the callback told about changes merged in from the file on disk
+    pub fn on_external_merge(
+        &mut self,
+        listener: impl Fn(&ExternalMergeReport) + Send + Sync + 'static,
+    ) {
+        self.on_external_merge = Some(Arc::new(listener));
+    }
+
+    /// Sets where the previous file is copied to before each save
+    pub fn set_backups(&mut self, backups: Option<BackupRotation>) {
+        self.backups = backups;
+    }
+
+    pub fn backups(&self) -> Option<&BackupRotation> {
+        self.backups.as_ref()
+    }
+
+    /// Changes whenever the open database does, so derived data can tell it is stale
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn db(&self) -> &Database {
+        &self.db
+    }
+
+    pub fn key(&self) -> &DatabaseKey {
+        &self.key
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Applies `f` to a copy of the database and persists the result.
+    /// The open database is left as it was if `f` or the save fails.
+    pub fn update<T>(
+        &mut self,
+        f: impl FnOnce(&mut Database) -> Result<T, CommonError>,
+    ) -> Result<T, CommonError> {
+        let mut db = self.db.clone();
+        let result = f(&mut db)?;
+        self.replace(db)?;
+        Ok(result)
+    }
+
+    /// Persists `db` and makes it the open database, first merging in
+    /// anything written to the file by another program since it was loaded
+    pub fn replace(&mut self, db: Database) -> Result<(), CommonError> {
+        let (db, report) = self.with_external_changes(db)?;
+
+        let data = encode(&db, &self.key)?;
+        self.write(&data)?;
+        self.db = db;
+        self.revision = next_revision();
+
+        self.notify_external_merge(report);
+        Ok(())
+    }
+
+    /// Re-encrypts the vault file with `key` and makes it the vault key.
+    /// The old file is kept next to it until the new one has been written and read back
+    /// with `key`, and is put back if either fails.
+    ///
+    /// Automatic backups under the old key are re-encrypted too, so they can still be
+    /// restored. Should that fail, the vault itself stays re-keyed and the error says so.
+    pub fn rekey(&mut self, key: DatabaseKey) -> Result<(), CommonError> {
+        let (mut db, report) = self.with_external_changes(self.db.clone())?;
+        db.meta.master_key_changed = Some(Times::now());
+        let data = encode(&db, &key)?;
+
+        let mut old = self.path.clone().into_os_string();
+        old.push(".old");
+        let old = PathBuf::from(old);
+        if self.path.exists() {
+            fs::copy(&self.path, &old).map_err(|e| {
+                CommonError::UnexpectedError(anyhow::anyhow!("Failed to back up KDBX: {}", e))
+            })?;
+        }
+        let written = write_atomic(&self.path, &data).and_then(|_| {
+            let stored = fs::read(&self.path).map_err(|e| {
+                CommonError::UnexpectedError(anyhow::anyhow!("Failed to read KDBX file: {}", e))
+            })?;
+            decode(&stored, &key).map(|_| ()).map_err(|e| {
+                CommonError::UnexpectedError(anyhow::anyhow!("Failed to verify KDBX: {}", e))
+            })
+        });
+        if let Err(e) = written {
+            if old.exists() {
+                let _ = fs::rename(&old, &self.path);
+            }
+            return Err(e);
+        }
+        let _ = fs::remove_file(&old);
+
+        self.fingerprint = Some(Fingerprint::of(&self.path, &data));
+        self.db = db;
+        let old_key = std::mem::replace(&mut self.key, key);
+        self.revision = next_revision();
+
+        self.notify_external_merge(report);
+        match &self.backups {
+            Some(backups) => backups.rekey(&self.path, &old_key, &self.key).map_err(|e| {
+                CommonError::UnexpectedError(anyhow::anyhow!(
+                    "Vault re-keyed, but its backups could not be re-encrypted: {}",
+                    e
+                ))
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Overwrites the vault with the KDBX file at `source` as it is, without
+    /// merging, failing with `InvalidMasterKey` when it uses another key
+    pub fn restore_from(&mut self, source: &Path) -> Result<(), CommonError> {
+        let data = fs::read(source).map_err(|e| {
+            CommonError::UnexpectedError(anyhow::anyhow!("Failed to read KDBX file: {}", e))
+        })?;
+        let db = decode(&data, &self.key).map_err(|e| match e {
+            DatabaseOpenError::Key(_) => CommonError::InvalidMasterKey,
+            e => CommonError::UnexpectedError(anyhow::anyhow!("Failed to read KDBX: {}", e)),
+        })?;
+
+        self.overwrite(&data)?;
+        self.db = db;
+        self.revision = next_revision();
+        Ok(())
+    }
+
+    /// Overwrites the vault with `db` as it is, without merging, encrypted with the vault key
+    pub fn restore(&mut self, db: Database) -> Result<(), CommonError> {
+        let data = encode(&db, &self.key)?;
+        self.overwrite(&data)?;
+        self.db = db;
+        self.revision = next_revision();
+        Ok(())
+    }
+
+    /// Writes the open database back to its file
+    pub fn save(&mut self) -> Result<(), CommonError> {
+        self.replace(self.db.clone())
+    }
+
+    /// The KDBX file contents for the open database, encrypted with the vault key
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CommonError> {
+        encode(&self.db, &self.key)
+    }
+
+    /// Merges into `db` whatever another program wrote to the file since this store last saw it
+    fn with_external_changes(
+        &self,
+        db: Database,
+    ) -> Result<(Database, Option<ExternalMergeReport>), CommonError> {
+        match self.external_changes()? {
+            Some(external) => {
+                let (merged, report) = merge_external(db, &external)?;
+                Ok((merged, Some(report)))
+            }
+            None => Ok((db, None)),
+        }
+    }
+
+    fn notify_external_merge(&self, report: Option<ExternalMergeReport>) {
+        if let (Some(report), Some(listener)) = (report, &self.on_external_merge) {
+            listener(&report);
+        }
+    }
+
+    /// Backs up the current file, then atomically replaces it with `data`
+    fn write(&mut self, data: &[u8]) -> Result<(), CommonError> {
+        if let Some(backups) = &self.backups {
+            backups.rotate(&self.path)?;
+        }
+        self.write_file(data)
+    }
+
+    /// Like [`VaultStore::write`] for restores, which back up the current file even when
+    /// backups are turned off, since nothing of it would be left otherwise
+    fn overwrite(&mut self, data: &[u8]) -> Result<(), CommonError> {
+        if let Some(backups) = &self.backups {
+            backups.snapshot(&self.path)?;
+        }
+        self.write_file(data)
+    }
+
+    fn write_file(&mut self, data: &[u8]) -> Result<(), CommonError> {
+        write_atomic(&self.path, data)?;
+        self.fingerprint = Some(Fingerprint::of(&self.path, data));
+        Ok(())
+    }
+
+    /// Reads the file back if it no longer matches what this store last saw.
+    /// Size and mtime are checked first; the hash settles touches that changed nothing.
+    fn external_changes(&self) -> Result<Option<Database>, CommonError> {
+        let Some(known) = &self.fingerprint else {
+            return Ok(None);
+        };
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(CommonError::UnexpectedError(anyhow::anyhow!(
+                    "Failed to stat KDBX file: {}",
+                    e
+                )))
+            }
+        };
+        if metadata.len() == known.len && metadata.modified().ok() == known.modified {
+            return Ok(None);
+        }
+
+        let data = fs::read(&self.path).map_err(|e| {
+            CommonError::UnexpectedError(anyhow::anyhow!("Failed to read KDBX file: {}", e))
+        })?;
+        if Sha256::digest(&data).as_slice() == known.hash.as_slice() {
+            return Ok(None);
+        }
+
+        // A file re-keyed elsewhere cannot be merged without the new key
+        decode(&data, &self.key).map(Some).map_err(|e| match e {
+            DatabaseOpenError::Key(_) => CommonError::InvalidMasterKey,
+            e => CommonError::UnexpectedError(anyhow::anyhow!("Failed to read KDBX: {}", e)),
+        })
+    }
+}
+
+/// Merges the on-disk database into `db`, newer entries winning, and describes what that changed
+fn merge_external(
+    db: Database,
+    external: &Database,
+) -> Result<(Database, ExternalMergeReport), CommonError> {
+    let mut merged = db.clone();
+    let log = merged.merge(external).map_err(|e| {
+        CommonError::UnexpectedError(anyhow::anyhow!("Failed to merge KDBX: {}", e))
+    })?;
+    merge_binaries(&mut merged, external);
+
+    let before = entries(&db.root.children);
+    let after = entries(&merged.root.children);
+    let mut report = ExternalMergeReport {
+        warnings: log.warnings,
+        ..Default::default()
+    };
+    for (uuid, entry) in &after {
+        match before.get(uuid) {
+            None => report.added.push(title(entry)),
+            Some(previous) if differs(previous, entry) => report.updated.push(title(entry)),
+            Some(_) => {}
+        }
+    }
+    for (uuid, entry) in &before {
+        if !after.contains_key(uuid) {
+            report.removed.push(title(entry));
+        }
+    }
+
+    Ok((merged, report))
+}
+
+/// Copies the inner header binaries of `other` that `db` lacks.
+/// `Database::merge` only merges the XML tree, so attachments of merged entries would be lost.
+pub fn merge_binaries(db: &mut Database, other: &Database) {
+    for attachment in &other.header_attachments {
+        if !db
+            .header_attachments
+            .iter()
+            .any(|a| a.content == attachment.content)
+        {
+            db.header_attachments.push(attachment.clone());
+        }
+    }
+}
+
+fn entries(nodes: &[Node]) -> HashMap<Uuid, &Entry> {
+    let mut result = HashMap::new();
+    for node in nodes {
+        match node {
+            Node::Group(g) => result.extend(entries(&g.children)),
+            Node::Entry(e) => {
+                result.insert(e.uuid, e);
+            }
+        }
+    }
+    result
+}
+
+fn title(entry: &Entry) -> String {
+    entry.get("Title").unwrap_or_default().to_string()
+}
+
+fn differs(a: &Entry, b: &Entry) -> bool {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    a.times = Times::default();
+    b.times = Times::default();
+    a != b
+}
+
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+fn decode(data: &[u8], key: &DatabaseKey) -> Result<Database, DatabaseOpenError> {
+    Database::open(&mut &data[..], key.clone())
+}
+
+fn encode(db: &Database, key: &DatabaseKey) -> Result<Vec<u8>, CommonError> {
+    let mut data = Vec::new();
+    db.save(&mut data, key.clone())
+        .map_err(|e| CommonError::UnexpectedError(anyhow::anyhow!("Failed to save KDBX: {}", e)))?;
+    Ok(data)
+}
+
+/// Atomic write: write to a uniquely named temp file next to `path`, fsync it,
+/// rename it over `path` and fsync the directory so the rename itself is durable
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<(), CommonError> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or(CommonError::UnexpectedError(anyhow::anyhow!(
+            "Invalid file path: {}",
+            path.display()
+        )))?;
+    let temp_path = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        Uuid::now_v7()
+    ));
+
+    let result = write_and_sync(&temp_path, data).and_then(|_| {
+        fs::rename(&temp_path, path).map_err(|e| {
+            CommonError::UnexpectedError(anyhow::anyhow!("Failed to replace file: {}", e))
+        })
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+
+    sync_dir(dir)
+}
+
+fn write_and_sync(path: &Path, data: &[u8]) -> Result<(), CommonError> {
+    let mut file = File::create(path).map_err(|e| {
+        CommonError::UnexpectedError(anyhow::anyhow!("Failed to create temp file: {}", e))
+    })?;
+    file.write_all(data)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| CommonError::UnexpectedError(anyhow::anyhow!("Failed to write file: {}", e)))
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<(), CommonError> {
+    File::open(dir).and_then(|d| d.sync_all()).map_err(|e| {
+        CommonError::UnexpectedError(anyhow::anyhow!("Failed to sync directory: {}", e))
+    })
+}
+
+/// Directories cannot be opened for syncing on Windows; NTFS journals the rename instead
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> Result<(), CommonError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepass::config::DatabaseConfig;
+    use keepass::db::Value;
+    use std::sync::Mutex;
+    use xpassword_config::BackupPolicy;
+
+    /// A scratch directory removed again when the test ends
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "xpassword-vault-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        pub(crate) fn join(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn key(password: &str) -> DatabaseKey {
+        DatabaseKey::new().with_password(password)
+    }
+
+    fn add_entry(db: &mut Database, title: &str) {
+        let mut entry = Entry::new();
+        entry
+            .fields
+            .insert("Title".to_string(), Value::Unprotected(title.to_string()));
+        db.root.children.push(Node::Entry(entry));
+    }
+
+    fn titles(db: &Database) -> Vec<String> {
+        let mut titles: Vec<String> = entries(&db.root.children)
+            .values()
+            .map(|e| title(e))
+            .collect();
+        titles.sort();
+        titles
+    }
+
+    fn create(path: PathBuf, password: &str) -> VaultStore {
+        VaultStore::create(
+            path,
+            Database::new(DatabaseConfig::default()),
+            key(password),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn merges_changes_another_program_saved() {
+        let dir = TempDir::new("external-merge");
+        let mut store = create(dir.join("vault.kdbx"), "secret");
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        store.on_external_merge(move |report| sink.lock().unwrap().push(report.clone()));
+
+        let mut other = VaultStore::open(dir.join("vault.kdbx"), key("secret")).unwrap();
+        other
+            .update(|db| {
+                add_entry(db, "theirs");
+                Ok(())
+            })
+            .unwrap();
+
+        store
+            .update(|db| {
+                add_entry(db, "ours");
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(titles(store.db()), ["ours", "theirs"]);
+        let reopened = VaultStore::open(dir.join("vault.kdbx"), key("secret")).unwrap();
+        assert_eq!(titles(reopened.db()), ["ours", "theirs"]);
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].added, ["theirs"]);
+        assert!(reports[0].removed.is_empty());
+    }
+
+    #[test]
+    fn saves_without_merging_when_the_file_is_unchanged() {
+        let dir = TempDir::new("no-merge");
+        let mut store = create(dir.join("vault.kdbx"), "secret");
+        let merges = Arc::new(AtomicU64::new(0));
+        let counter = merges.clone();
+        store.on_external_merge(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let revision = store.revision();
+        store
+            .update(|db| {
+                add_entry(db, "ours");
+                Ok(())
+            })
+            .unwrap();
+        store.save().unwrap();
+
+        assert_eq!(merges.load(Ordering::Relaxed), 0);
+        assert_ne!(store.revision(), revision);
+        assert_eq!(titles(store.db()), ["ours"]);
+    }
+
+    #[test]
+    fn refuses_to_overwrite_a_file_re_keyed_elsewhere() {
+        let dir = TempDir::new("external-rekey");
+        let mut store = create(dir.join("vault.kdbx"), "secret");
+        let mut other = VaultStore::open(dir.join("vault.kdbx"), key("secret")).unwrap();
+        other.rekey(key("changed")).unwrap();
+
+        let result = store.update(|db| {
+            add_entry(db, "ours");
+            Ok(())
+        });
+
+        assert!(matches!(result, Err(CommonError::InvalidMasterKey)));
+        assert!(titles(store.db()).is_empty());
+        assert!(VaultStore::open(dir.join("vault.kdbx"), key("changed")).is_ok());
+    }
+
+    #[test]
+    fn rekey_re_encrypts_the_backups() {
+        let dir = TempDir::new("rekey-backups");
+        let mut store = create(dir.join("vault.kdbx"), "secret");
+        store.set_backups(Some(BackupRotation::new(
+            dir.join("backups"),
+            BackupPolicy {
+                enabled: true,
+                ..Default::default()
+            },
+        )));
+        store
+            .update(|db| {
+                add_entry(db, "ours");
+                Ok(())
+            })
+            .unwrap();
+
+        store.rekey(key("changed")).unwrap();
+
+        let backups = store.backups().unwrap().list(store.path()).unwrap();
+        assert_eq!(backups.len(), 1);
+        let backup = store
+            .backups()
+            .unwrap()
+            .resolve(store.path(), &backups[0].name)
+            .unwrap();
+        store.restore_from(&backup).unwrap();
+        assert!(titles(store.db()).is_empty());
+        assert!(VaultStore::open(dir.join("vault.kdbx"), key("changed")).is_ok());
+    }
+
+    #[test]
+    fn restore_backs_up_the_vault_even_with_backups_disabled() {
+        let dir = TempDir::new("restore-snapshot");
+        let mut store = create(dir.join("vault.kdbx"), "secret");
+        store.set_backups(Some(BackupRotation::new(
+            dir.join("backups"),
+            BackupPolicy {
+                enabled: false,
+                ..Default::default()
+            },
+        )));
+        store
+            .update(|db| {
+                add_entry(db, "ours");
+                Ok(())
+            })
+            .unwrap();
+        assert!(store
+            .backups()
+            .unwrap()
+            .list(store.path())
+            .unwrap()
+            .is_empty());
+
+        store
+            .restore(Database::new(DatabaseConfig::default()))
+            .unwrap();
+
+        assert!(titles(store.db()).is_empty());
+        let backups = store.backups().unwrap().list(store.path()).unwrap();
+        assert_eq!(backups.len(), 1);
+        let backup = store
+            .backups()
+            .unwrap()
+            .resolve(store.path(), &backups[0].name)
+            .unwrap();
+        let copy = VaultStore::open(backup, key("secret")).unwrap();
+        assert_eq!(titles(copy.db()), vec!["ours".to_string()]);
+    }
+
+    #[test]
+    fn rekey_keeps_the_old_key_when_the_file_cannot_be_replaced() {
+        let dir = TempDir::new("rekey-rollback");
+        let mut store = create(dir.join("vault.kdbx"), "secret");
+        fs::create_dir(dir.join("vault.kdbx.old")).unwrap();
+
+        assert!(store.rekey(key("changed")).is_err());
+
+        assert!(*store.key() == key("secret"));
+        assert!(VaultStore::open(dir.join("vault.kdbx"), key("secret")).is_ok());
+        store.save().unwrap();
+    }
+
+    #[test]
+    fn leaves_the_open_database_alone_when_an_update_fails() {
+        let dir = TempDir::new("failed-update");
+        let mut store = create(dir.join("vault.kdbx"), "secret");
+
+        let result: Result<(), _> = store.update(|db| {
+            add_entry(db, "ours");
+            Err(CommonError::RequestError("no".to_string()))
+        });
+
+        assert!(result.is_err());
+        assert!(titles(store.db()).is_empty());
+    }
+}
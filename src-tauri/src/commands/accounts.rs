@@ -1,111 +1,285 @@
+use crate::commands::audit;
+use crate::commands::groups::{add_entry, move_entry};
+use crate::commands::history;
+use crate::commands::recycle_bin::{self, active_entries};
+use crate::commands::tags::set_tags;
 use crate::state::AppState;
-use keepass::db::{Entry, Group, Node, Value};
-use log::info;
+use crate::utils::otp::{counter_overflow, Otp, OtpKind, HOTP_COUNTER_FIELD};
+use crate::utils::recovery_codes::{self, RECOVERY_CODES_FIELD};
+use keepass::db::{Entry, Group, Node, Times, Value};
+use keepass::Database;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tauri::Manager;
-use xpassword_entity::{Account, CreateAccountRequest, UpdateAccountRequest};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use xpassword_entity::{
+    AccountSummary, AuditAction, CreateAccountRequest, CustomField, OtpCode, OtpParams, OtpType,
+    SearchRequest, SearchResult, SecretString, UpdateAccountRequest,
+};
 use xpassword_error::CommonError;
 
-/// List all groups from the database
+/// Fields the app manages itself; any other string field is a custom field
+const STANDARD_FIELDS: [&str; 8] = [
+    "Title",
+    "UserName",
+    "Password",
+    "URL",
+    "Notes",
+    "TOTP",
+    HOTP_COUNTER_FIELD,
+    RECOVERY_CODES_FIELD,
+];
+
+/// How long a copied password stays on the clipboard
+const CLIPBOARD_CLEAR_DELAY: Duration = Duration::from_secs(30);
+
+/// List all accounts from the database, except those in the recycle bin
 #[tauri::command]
-pub async fn list_groups(app: tauri::AppHandle) -> Result<Vec<keepass::db::Group>, CommonError> {
+pub async fn list_accounts(app: tauri::AppHandle) -> Result<Vec<AccountSummary>, CommonError> {
     let state = app.state::<Arc<Mutex<AppState>>>();
     let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
 
-    if app_state.is_locked {
-        return Err(CommonError::AppIsLocked);
-    }
-    let db = app_state
-        .db
-        .as_ref()
-        .ok_or(CommonError::KdbxNotInitialized)?;
-    let root_node = &db.root;
-    let g = groups(&root_node.children);
-    info!("{:?}", g);
-    Ok(g)
+    let entries = active_entries(app_state.vault()?.db());
+    let accounts = entries
+        .into_iter()
+        .map(|e| map_entry_to_summary(&e))
+        .collect();
+
+    Ok(accounts)
 }
 
-/// List all tags from the database
+/// Search accounts with the query DSL, returning one page of matches
 #[tauri::command]
-pub async fn list_tags(app: tauri::AppHandle) -> Result<Vec<String>, CommonError> {
+pub async fn search_accounts(
+    app: tauri::AppHandle,
+    request: SearchRequest,
+) -> Result<SearchResult, CommonError> {
     let state = app.state::<Arc<Mutex<AppState>>>();
-    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
-    if app_state.is_locked {
-        return Err(CommonError::AppIsLocked);
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    let (ids, total) = app_state.search_index()?.search(&request);
+    let db = app_state.vault()?.db();
+    let accounts = ids
+        .iter()
+        .filter_map(|id| find_entry(&db.root, id))
+        .map(map_entry_to_summary)
+        .collect();
+
+    Ok(SearchResult { total, accounts })
+}
+
+fn map_entry_to_summary(entry: &Entry) -> AccountSummary {
+    let get_field = |key: &str| entry.get(key).unwrap_or_default().to_string();
+
+    AccountSummary {
+        id: entry.uuid.to_string(),
+        title: get_field("Title"),
+        username: get_field("UserName"),
+        url: get_field("URL"),
+        notes: if is_protected(entry, "Notes") {
+            String::new()
+        } else {
+            get_field("Notes")
+        },
+        notes_protected: is_protected(entry, "Notes"),
+        has_password: entry.get("Password").is_some_and(|p| !p.is_empty()),
+        otp_type: entry
+            .get("TOTP")
+            .and_then(|t| t.parse::<OtpParams>().ok())
+            .map(|otp| otp.otp_type),
+        hotp_counter: hotp_counter(entry),
+        tags: entry.tags.clone(),
+        custom_fields: custom_fields(entry),
+        recovery_codes: recovery_codes::count(entry),
     }
-    let db = app_state
-        .db
-        .as_ref()
-        .ok_or(CommonError::KdbxNotInitialized)?;
+}
 
-    let root_node = &db.root;
+fn is_protected(entry: &Entry, key: &str) -> bool {
+    matches!(entry.fields.get(key), Some(Value::Protected(_)))
+}
 
-    let mut tags: Vec<String> = vec![];
+/// The entry's custom string fields by key, with protected values left out
+fn custom_fields(entry: &Entry) -> Vec<CustomField> {
+    let mut fields: Vec<CustomField> = entry
+        .fields
+        .iter()
+        .filter(|(key, _)| !STANDARD_FIELDS.contains(&key.as_str()))
+        .filter_map(|(key, value)| match value {
+            Value::Unprotected(value) => Some(CustomField {
+                key: key.clone(),
+                value: value.clone(),
+                protected: false,
+            }),
+            Value::Protected(_) => Some(CustomField {
+                key: key.clone(),
+                value: String::new(),
+                protected: true,
+            }),
+            Value::Bytes(_) => None,
+        })
+        .collect();
+    fields.sort_by(|a, b| a.key.cmp(&b.key));
+    fields
+}
 
-    for node in &root_node.children {
-        if let Node::Entry(e) = node {
-            tags.extend(e.tags.clone())
+/// Replaces the custom string fields of an entry, leaving standard and binary fields alone
+fn set_custom_fields(entry: &mut Entry, fields: &[CustomField]) -> Result<(), CommonError> {
+    let mut values = Vec::with_capacity(fields.len());
+    for field in fields {
+        let key = field.key.trim();
+        if key.is_empty() || STANDARD_FIELDS.contains(&key) {
+            return Err(CommonError::RequestError(format!(
+                "invalid custom field name: {:?}",
+                field.key
+            )));
+        }
+        if values.iter().any(|(k, _)| k == key) {
+            return Err(CommonError::RequestError(format!(
+                "duplicate custom field: {}",
+                key
+            )));
         }
+
+        let value = match entry.fields.get(key) {
+            Some(existing @ Value::Protected(_)) if field.protected && field.value.is_empty() => {
+                existing.clone()
+            }
+            _ if field.protected => Value::Protected(field.value.clone().into_bytes().into()),
+            _ => Value::Unprotected(field.value.clone()),
+        };
+        values.push((key.to_string(), value));
     }
-    tags.sort();
-    tags.dedup();
 
-    Ok(tags)
+    entry.fields.retain(|key, value| {
+        STANDARD_FIELDS.contains(&key.as_str()) || matches!(value, Value::Bytes(_))
+    });
+    entry.fields.extend(values);
+    Ok(())
+}
+
+/// Sets a standard field, keeping it protected if it already was
+fn set_field(entry: &mut Entry, key: &str, value: &str) {
+    let value = if is_protected(entry, key) {
+        Value::Protected(value.as_bytes().into())
+    } else {
+        Value::Unprotected(value.to_string())
+    };
+    entry.fields.insert(key.to_string(), value);
 }
 
-/// List all accounts from the database
+/// Reveal a single field of an account, such as a protected custom field or protected notes.
+/// The access is recorded in the audit trail.
 #[tauri::command]
-pub async fn list_accounts(app: tauri::AppHandle) -> Result<Vec<Account>, CommonError> {
+pub fn reveal_field(
+    app: tauri::AppHandle,
+    account_id: String,
+    key: String,
+) -> Result<String, CommonError> {
     let state = app.state::<Arc<Mutex<AppState>>>();
     let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
 
-    if app_state.is_locked {
-        return Err(CommonError::AppIsLocked);
-    }
-    let db = app_state
-        .db
-        .as_ref()
-        .ok_or(CommonError::KdbxNotInitialized)?;
-    let root_node = &db.root;
+    let entry = find_entry(&app_state.vault()?.db().root, &account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+    let value = entry
+        .get(&key)
+        .ok_or(CommonError::RequestError("Field not found".to_string()))?;
+    audit::record(&app, AuditAction::RevealField, entry, Some(&key))?;
 
-    let entries = entries(&root_node.children);
-    let accounts = entries
-        .into_iter()
-        .map(|e| map_entry_to_account(&e))
-        .collect();
+    Ok(value.to_string())
+}
 
-    Ok(accounts)
+/// Reveal the password of a single account. The access is recorded in the audit trail.
+#[tauri::command]
+pub fn reveal_password(app: tauri::AppHandle, account_id: String) -> Result<String, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    let entry = find_entry(&app_state.vault()?.db().root, &account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+    audit::record(&app, AuditAction::RevealPassword, entry, None)?;
+
+    Ok(entry.get("Password").unwrap_or_default().to_string())
 }
 
-fn map_entry_to_account(entry: &Entry) -> Account {
-    let get_field = |key: &str| -> String {
-        entry
-            .fields
-            .get(key)
-            .map(|v| match v {
-                Value::Unprotected(s) => s.clone(),
-                Value::Protected(s) => String::from_utf8(s.unsecure().to_vec()).unwrap_or_default(),
-                Value::Bytes(_) => String::new(),
-            })
-            .unwrap_or_default()
+/// Copy the password of a single account to the clipboard without sending it to the webview.
+/// The clipboard is cleared again after a while unless something else was copied meanwhile.
+#[tauri::command]
+pub fn copy_password(app: tauri::AppHandle, account_id: String) -> Result<(), CommonError> {
+    let password = {
+        let state = app.state::<Arc<Mutex<AppState>>>();
+        let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+        let entry = find_entry(&app_state.vault()?.db().root, &account_id)
+            .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+        audit::record(&app, AuditAction::CopyPassword, entry, None)?;
+        SecretString::from(entry.get("Password").unwrap_or_default())
     };
 
-    Account {
-        id: entry.uuid.to_string(),
-        title: get_field("Title"),
-        username: get_field("UserName"),
-        password: get_field("Password"),
-        url: get_field("URL"),
-        notes: get_field("Notes"),
-        totp: entry.fields.get("TOTP").map(|v| match v {
-            Value::Unprotected(s) => s.clone(),
-            Value::Protected(s) => String::from_utf8(s.unsecure().to_vec()).unwrap_or_default(),
-            Value::Bytes(_) => String::new(),
-        }),
+    app.clipboard()
+        .write_text(password.to_string())
+        .map_err(|e| {
+            CommonError::UnexpectedError(anyhow::anyhow!("Failed to copy password: {}", e))
+        })?;
+
+    std::thread::spawn(move || {
+        std::thread::sleep(CLIPBOARD_CLEAR_DELAY);
+        if app
+            .clipboard()
+            .read_text()
+            .is_ok_and(|text| *text == *password)
+        {
+            let _ = app.clipboard().clear();
+        }
+    });
+
+    Ok(())
+}
+
+fn hotp_counter(entry: &Entry) -> Option<u64> {
+    entry
+        .get(HOTP_COUNTER_FIELD)
+        .and_then(|c| c.trim().parse().ok())
+}
+
+/// Parse the requested OTP value, rejecting anything that is not a valid secret or URI
+fn parse_otp(totp: Option<&str>) -> Result<Option<OtpParams>, CommonError> {
+    totp.map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(str::parse)
+        .transpose()
+}
+
+/// Store the canonical OTP URI and keep the HOTP counter field in step with it
+fn set_otp_fields(entry: &mut Entry, otp: Option<&OtpParams>) {
+    let Some(otp) = otp else {
+        entry.fields.remove("TOTP");
+        entry.fields.remove(HOTP_COUNTER_FIELD);
+        return;
+    };
+
+    let uri = otp.to_string();
+    let changed = entry.get("TOTP") != Some(uri.as_str());
+    entry
+        .fields
+        .insert("TOTP".to_string(), Value::Unprotected(uri));
+
+    match otp.otp_type {
+        OtpType::Hotp => {
+            // A new secret starts over from the counter in its URI
+            if changed || hotp_counter(entry).is_none() {
+                entry.fields.insert(
+                    HOTP_COUNTER_FIELD.to_string(),
+                    Value::Unprotected(otp.counter.to_string()),
+                );
+            }
+        }
+        OtpType::Totp | OtpType::Steam => {
+            entry.fields.remove(HOTP_COUNTER_FIELD);
+        }
     }
 }
 
-fn entries(nodes: &Vec<Node>) -> Vec<Entry> {
+pub(crate) fn entries(nodes: &Vec<Node>) -> Vec<Entry> {
     let mut result: Vec<Entry> = Vec::new();
     for node in nodes {
         if let Node::Group(g) = node {
@@ -117,39 +291,40 @@ fn entries(nodes: &Vec<Node>) -> Vec<Entry> {
     }
     result
 }
-fn groups(nodes: &Vec<Node>) -> Vec<Group> {
-    let mut result: Vec<Group> = Vec::new();
-    for node in nodes {
-        if let Node::Group(g) = node {
-            let subgroups = groups(&g.children);
-            let mut group_clone = g.clone();
-            group_clone.children = subgroups.iter().cloned().map(Node::Group).collect();
-            result.push(group_clone);
+
+pub(crate) fn find_entry<'a>(group: &'a Group, id: &str) -> Option<&'a Entry> {
+    for node in &group.children {
+        match node {
+            Node::Entry(e) if e.uuid.to_string() == id => return Some(e),
+            Node::Group(g) => {
+                if let Some(e) = find_entry(g, id) {
+                    return Some(e);
+                }
+            }
+            _ => {}
         }
     }
-    result
+    None
 }
 
-/// Create a new account
-#[tauri::command]
-pub async fn create_account(
-    app: tauri::AppHandle,
-    request: CreateAccountRequest,
-) -> Result<(), CommonError> {
-    let state = app.state::<Arc<Mutex<AppState>>>();
-    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
-
-    if app_state.is_locked {
-        return Err(CommonError::AppIsLocked);
+pub(crate) fn find_entry_mut<'a>(group: &'a mut Group, id: &str) -> Option<&'a mut Entry> {
+    for node in &mut group.children {
+        match node {
+            Node::Entry(e) if e.uuid.to_string() == id => return Some(e),
+            Node::Group(g) => {
+                if let Some(e) = find_entry_mut(g, id) {
+                    return Some(e);
+                }
+            }
+            _ => {}
+        }
     }
+    None
+}
 
-    let master_password = app_state.master_password.clone();
-    let kdbx_path = app_state.config.builder().kdbx_path.clone();
-
-    let db = app_state
-        .db
-        .as_mut()
-        .ok_or(CommonError::KdbxNotInitialized)?;
+/// Build a new entry from a create request, validating its OTP value
+pub(crate) fn new_entry(request: CreateAccountRequest) -> Result<Entry, CommonError> {
+    let otp = parse_otp(request.totp.as_deref())?;
 
     let mut entry = Entry::new();
     entry
@@ -160,274 +335,229 @@ pub async fn create_account(
         .insert("UserName".to_string(), Value::Unprotected(request.username));
     entry.fields.insert(
         "Password".to_string(),
-        Value::Protected(request.password.into_bytes().into()),
+        Value::Protected(request.password.as_bytes().into()),
     );
     entry
         .fields
         .insert("URL".to_string(), Value::Unprotected(request.url));
-    entry
-        .fields
-        .insert("Notes".to_string(), Value::Unprotected(request.notes));
+    set_notes(&mut entry, request.notes, request.notes_protected);
 
-    if let Some(totp) = request.totp {
-        if !totp.is_empty() {
-            entry
-                .fields
-                .insert("TOTP".to_string(), Value::Unprotected(totp));
-        }
-    }
+    set_otp_fields(&mut entry, otp.as_ref());
+    set_tags(&mut entry, &request.tags);
+    set_custom_fields(&mut entry, &request.custom_fields)?;
+    recovery_codes::replace(&mut entry, &request.recovery_codes);
 
-    // Add to root group for now
-    db.root.children.push(Node::Entry(entry));
-
-    // Save database
-    // Note: In a real app we should save to file here.
-    // Assuming there is a save mechanism or we need to implement it.
-    // For now, we just modify the in-memory state.
-    // To persist, we would need the password and path which are in AppState/Config.
-
-    // Let's try to save if we have the master password
-    if let Some(password) = master_password {
-        // Atomic write: write to temp file, then rename
-        let temp_path = kdbx_path.with_extension("tmp");
-        let mut file = std::fs::File::create(&temp_path).map_err(|e| {
-            CommonError::UnexpectedError(anyhow::anyhow!("Failed to create temp KDBX file: {}", e))
-        })?;
-        db.save(
-            &mut file,
-            keepass::DatabaseKey::new().with_password(&password),
-        )
-        .map_err(|e| CommonError::UnexpectedError(anyhow::anyhow!("Failed to save KDBX: {}", e)))?;
-        file.sync_all().map_err(|e| {
-            CommonError::UnexpectedError(anyhow::anyhow!("Failed to sync KDBX: {}", e))
-        })?;
-        drop(file);
-        std::fs::rename(&temp_path, &kdbx_path).map_err(|e| {
-            CommonError::UnexpectedError(anyhow::anyhow!("Failed to replace KDBX file: {}", e))
-        })?;
-    }
+    Ok(entry)
+}
 
-    Ok(())
+/// Create a new account
+#[tauri::command]
+pub async fn create_account(
+    app: tauri::AppHandle,
+    request: CreateAccountRequest,
+) -> Result<(), CommonError> {
+    let group_id = request.group_id.clone();
+    let entry = new_entry(request)?;
+
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    app_state
+        .vault_mut()?
+        .update(|db| add_entry(db, entry, group_id.as_deref()))
 }
 
-/// Update an existing account
+/// Update an existing account, keeping its previous version in the entry history
 #[tauri::command]
 pub async fn update_account(
     app: tauri::AppHandle,
     request: UpdateAccountRequest,
 ) -> Result<(), CommonError> {
+    let otp = request
+        .totp
+        .as_deref()
+        .map(|totp| parse_otp(Some(totp)))
+        .transpose()?;
+
     let state = app.state::<Arc<Mutex<AppState>>>();
     let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let limits = app_state.config.builder().settings.history.clone();
+
+    app_state.vault_mut()?.update(|db| {
+        let entry = find_entry_mut(&mut db.root, &request.id)
+            .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+        let previous = entry.clone();
+        update_entry(entry, &request, otp.as_ref())?;
+        history::record(db, &request.id, previous, &limits)?;
+
+        match request.group_id.as_deref() {
+            Some(group_id) => move_entry(db, &request.id, Some(group_id)),
+            None => Ok(()),
+        }
+    })
+}
 
-    if app_state.is_locked {
-        return Err(CommonError::AppIsLocked);
+/// Applies an update request to an entry.
+/// `otp` is `None` to leave the OTP fields alone and `Some(None)` to remove them.
+fn update_entry(
+    entry: &mut Entry,
+    request: &UpdateAccountRequest,
+    otp: Option<&Option<OtpParams>>,
+) -> Result<(), CommonError> {
+    if let Some(custom_fields) = &request.custom_fields {
+        set_custom_fields(entry, custom_fields)?;
     }
 
-    let master_password = app_state.master_password.clone();
-    let kdbx_path = app_state.config.builder().kdbx_path.clone();
-
-    let db = app_state
-        .db
-        .as_mut()
-        .ok_or(CommonError::KdbxNotInitialized)?;
-
-    // Find and update entry
-    let mut found = false;
-    for child in &mut db.root.children {
-        if update_entry_recursive(child, &request) {
-            found = true;
-            break;
-        }
+    set_field(entry, "Title", &request.title);
+    set_field(entry, "UserName", &request.username);
+    if let Some(password) = &request.password {
+        entry.fields.insert(
+            "Password".to_string(),
+            Value::Protected(password.as_bytes().into()),
+        );
     }
-
-    if found {
-        // Save database
-        if let Some(password) = master_password {
-            // Atomic write: write to temp file, then rename
-            let temp_path = kdbx_path.with_extension("tmp");
-            let mut file = std::fs::File::create(&temp_path).map_err(|e| {
-                CommonError::UnexpectedError(anyhow::anyhow!(
-                    "Failed to create temp KDBX file: {}",
-                    e
-                ))
-            })?;
-            db.save(
-                &mut file,
-                keepass::DatabaseKey::new().with_password(&password),
-            )
-            .map_err(|e| {
-                CommonError::UnexpectedError(anyhow::anyhow!("Failed to save KDBX: {}", e))
-            })?;
-            file.sync_all().map_err(|e| {
-                CommonError::UnexpectedError(anyhow::anyhow!("Failed to sync KDBX: {}", e))
-            })?;
-            drop(file);
-            std::fs::rename(&temp_path, &kdbx_path).map_err(|e| {
-                CommonError::UnexpectedError(anyhow::anyhow!("Failed to replace KDBX file: {}", e))
-            })?;
+    set_field(entry, "URL", &request.url);
+
+    // Protected notes are not listed, so an empty value means they were left untouched
+    let protect_notes = request
+        .notes_protected
+        .unwrap_or_else(|| is_protected(entry, "Notes"));
+    let notes = match entry.get("Notes") {
+        Some(notes) if is_protected(entry, "Notes") && request.notes.is_empty() => {
+            notes.to_string()
         }
-        Ok(())
-    } else {
-        Err(CommonError::RequestError("Account not found".to_string()))
+        _ => request.notes.clone(),
+    };
+    set_notes(entry, notes, protect_notes);
+
+    if let Some(otp) = otp {
+        set_otp_fields(entry, otp.as_ref());
+    }
+    if let Some(tags) = &request.tags {
+        set_tags(entry, tags);
     }
+    if let Some(codes) = &request.recovery_codes {
+        recovery_codes::replace(entry, codes);
+    }
+    Ok(())
 }
 
-fn update_entry_recursive(node: &mut Node, request: &UpdateAccountRequest) -> bool {
-    match node {
-        Node::Group(g) => {
-            for child in &mut g.children {
-                if update_entry_recursive(child, request) {
-                    return true;
-                }
-            }
-            false
-        }
-        Node::Entry(e) => {
-            if e.uuid.to_string() == request.id {
-                e.fields.insert(
-                    "Title".to_string(),
-                    Value::Unprotected(request.title.clone()),
-                );
-                e.fields.insert(
-                    "UserName".to_string(),
-                    Value::Unprotected(request.username.clone()),
-                );
-                e.fields.insert(
-                    "Password".to_string(),
-                    Value::Protected(request.password.clone().into_bytes().into()),
-                );
-                e.fields
-                    .insert("URL".to_string(), Value::Unprotected(request.url.clone()));
-                e.fields.insert(
-                    "Notes".to_string(),
-                    Value::Unprotected(request.notes.clone()),
-                );
-
-                if let Some(totp) = &request.totp {
-                    if !totp.is_empty() {
-                        e.fields
-                            .insert("TOTP".to_string(), Value::Unprotected(totp.clone()));
-                    } else {
-                        e.fields.remove("TOTP");
-                    }
-                } else {
-                    e.fields.remove("TOTP");
-                }
-                true
-            } else {
-                false
-            }
-        }
-    }
+fn set_notes(entry: &mut Entry, notes: String, protected: bool) {
+    let value = if protected {
+        Value::Protected(notes.into_bytes().into())
+    } else {
+        Value::Unprotected(notes)
+    };
+    entry.fields.insert("Notes".to_string(), value);
 }
 
-/// Delete an account
+/// Delete an account into the recycle bin, or for good when it is already there or the bin
+/// is disabled
 #[tauri::command]
 pub async fn delete_account(app: tauri::AppHandle, account_id: String) -> Result<(), CommonError> {
     let state = app.state::<Arc<Mutex<AppState>>>();
     let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
 
-    if app_state.is_locked {
-        return Err(CommonError::AppIsLocked);
-    }
-
-    let master_password = app_state.master_password.clone();
-    let kdbx_path = app_state.config.builder().kdbx_path.clone();
-
-    let db = app_state
-        .db
-        .as_mut()
-        .ok_or(CommonError::KdbxNotInitialized)?;
-
-    if delete_entry_recursive(&mut db.root, &account_id) {
-        // Save database
-        if let Some(password) = master_password {
-            // Atomic write: write to temp file, then rename
-            let temp_path = kdbx_path.with_extension("tmp");
-            let mut file = std::fs::File::create(&temp_path).map_err(|e| {
-                CommonError::UnexpectedError(anyhow::anyhow!(
-                    "Failed to create temp KDBX file: {}",
-                    e
-                ))
-            })?;
-            db.save(
-                &mut file,
-                keepass::DatabaseKey::new().with_password(&password),
-            )
-            .map_err(|e| {
-                CommonError::UnexpectedError(anyhow::anyhow!("Failed to save KDBX: {}", e))
-            })?;
-            file.sync_all().map_err(|e| {
-                CommonError::UnexpectedError(anyhow::anyhow!("Failed to sync KDBX: {}", e))
-            })?;
-            drop(file);
-            std::fs::rename(&temp_path, &kdbx_path).map_err(|e| {
-                CommonError::UnexpectedError(anyhow::anyhow!("Failed to replace KDBX file: {}", e))
-            })?;
+    app_state.vault_mut()?.update(|db| {
+        if recycle_bin::delete_node(
+            db,
+            &|node| matches!(node, Node::Entry(e) if e.uuid.to_string() == account_id),
+        )? {
+            Ok(())
+        } else {
+            Err(CommonError::RequestError("Account not found".to_string()))
         }
-        Ok(())
-    } else {
-        Err(CommonError::RequestError("Account not found".to_string()))
-    }
+    })
 }
 
-fn delete_entry_recursive(group: &mut Group, account_id: &str) -> bool {
-    let initial_len = group.children.len();
-    group.children.retain(|node| match node {
-        Node::Entry(e) => e.uuid.to_string() != account_id,
-        _ => true,
-    });
+/// Generate the current TOTP code, or the next HOTP code, for an account
+#[tauri::command]
+pub fn get_code(app: tauri::AppHandle, account_id: String) -> Result<OtpCode, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let vault = app_state.vault_mut()?;
 
-    if group.children.len() < initial_len {
-        return true;
-    }
+    let entry = find_entry(&vault.db().root, &account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
 
-    for node in &mut group.children {
-        if let Node::Group(g) = node {
-            if delete_entry_recursive(g, account_id) {
-                return true;
-            }
+    let otp = Otp::parse(
+        entry
+            .get("TOTP")
+            .filter(|v| !v.is_empty())
+            .ok_or(CommonError::RequestError("TOTP not configured".to_string()))?,
+    )?;
+
+    let counter = match otp.kind() {
+        OtpKind::Totp { period } => {
+            let now = chrono::Local::now().timestamp() as u64;
+            return Ok(otp.totp(period, now));
         }
-    }
+        OtpKind::Steam { period } => {
+            let now = chrono::Local::now().timestamp() as u64;
+            return Ok(otp.steam(period, now));
+        }
+        OtpKind::Hotp { counter } => hotp_counter(entry).unwrap_or(counter),
+    };
 
-    false
+    // Every HOTP code handed out must advance the persisted counter first
+    let next = counter.checked_add(1).ok_or_else(counter_overflow)?;
+    vault.update(|db| set_hotp_counter(db, &account_id, next))?;
+
+    Ok(OtpCode {
+        code: otp.hotp(counter),
+        otp_type: OtpType::Hotp,
+        period: None,
+        remaining: None,
+        counter: Some(counter),
+    })
 }
 
-/// Generate TOTP code for an account
+/// Resynchronise an HOTP account from two consecutive codes shown by the token
 #[tauri::command]
-pub fn get_code(app: tauri::AppHandle, account_id: String) -> Result<String, CommonError> {
+pub fn resync_hotp(
+    app: tauri::AppHandle,
+    account_id: String,
+    first_code: String,
+    second_code: String,
+) -> Result<u64, CommonError> {
     let state = app.state::<Arc<Mutex<AppState>>>();
-    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let vault = app_state.vault_mut()?;
 
-    if app_state.is_locked {
-        return Err(CommonError::AppIsLocked);
-    }
+    let entry = find_entry(&vault.db().root, &account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+
+    let otp = Otp::parse(
+        entry
+            .get("TOTP")
+            .filter(|v| !v.is_empty())
+            .ok_or(CommonError::RequestError("HOTP not configured".to_string()))?,
+    )?;
+    let OtpKind::Hotp { counter } = otp.kind() else {
+        return Err(CommonError::RequestError(
+            "Account is not an HOTP account".to_string(),
+        ));
+    };
 
-    let db = app_state
-        .db
-        .as_ref()
-        .ok_or(CommonError::KdbxNotInitialized)?;
+    let from = hotp_counter(entry).unwrap_or(counter);
+    let next = otp
+        .resync(from, first_code.trim(), second_code.trim())?
+        .ok_or(CommonError::RequestError(
+            "Codes do not match any counter in the resync window".to_string(),
+        ))?;
 
-    // We need to find the entry and get the TOTP field
-    // Since we don't have a direct way to find by UUID in this structure without traversal
-    // We'll reuse a traversal helper or write a new one.
-    // For read-only we can use the `entries` helper and find.
+    vault.update(|db| set_hotp_counter(db, &account_id, next))?;
 
-    let root_node = &db.root;
-    let all_entries = entries(&root_node.children);
+    Ok(next)
+}
 
-    if let Some(entry) = all_entries
-        .iter()
-        .find(|e| e.uuid.to_string() == account_id)
-    {
-        if let Some(Value::Unprotected(totp_url)) = entry.fields.get("TOTP") {
-            // TODO: Implement actual TOTP code generation. Currently returns the raw TOTP secret.
-            // Consider adding a TOTP library like `totp-lite` or similar.
-            Ok(totp_url.clone())
-        } else {
-            Err(CommonError::RequestError("TOTP not configured".to_string()))
-        }
-    } else {
-        Err(CommonError::RequestError("Account not found".to_string()))
-    }
+fn set_hotp_counter(db: &mut Database, account_id: &str, counter: u64) -> Result<(), CommonError> {
+    let entry = find_entry_mut(&mut db.root, account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+    entry.fields.insert(
+        HOTP_COUNTER_FIELD.to_string(),
+        Value::Unprotected(counter.to_string()),
+    );
+    entry.times.set_last_modification(Times::now());
+    Ok(())
 }
@@ -0,0 +1,391 @@
+use crate::commands::accounts::{find_entry, find_entry_mut};
+use crate::commands::audit;
+use crate::commands::history;
+use crate::state::AppState;
+use keepass::db::{CustomDataItem, Entry, Group, HeaderAttachment, Node, Times, Value};
+use keepass::Database;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use xpassword_config::{AttachmentLimits, HistoryLimits};
+use xpassword_entity::{AttachmentSummary, AuditAction};
+use xpassword_error::CommonError;
+use xpassword_vault::write_atomic;
+
+/// Custom data key prefix linking an entry to a file in the KDBX4 binary pool.
+/// The keepass crate drops `<Binary>` references when reading entries, so the link is
+/// kept in the entry's custom data as the SHA-256 of the content instead. Other KeePass
+/// clients do not know this link: they show none of the files on the entry and may drop
+/// them from the pool as unused when they save the vault, and attachments added there are
+/// not visible here. The UI and README warn about this.
+const ATTACHMENT_PREFIX: &str = "XPassword.Attachment.";
+
+/// List the files attached to an account
+#[tauri::command]
+pub async fn list_attachments(
+    app: tauri::AppHandle,
+    account_id: String,
+) -> Result<Vec<AttachmentSummary>, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let db = app_state.vault()?.db();
+
+    let entry = find_entry(&db.root, &account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+    let mut result: Vec<AttachmentSummary> = attachments(entry)
+        .filter_map(|(name, hash)| {
+            Some(AttachmentSummary {
+                name: name.to_string(),
+                size: content(db, hash)?.len() as u64,
+            })
+        })
+        .collect();
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(result)
+}
+
+/// Attach a file to an account, replacing any attachment with the same name.
+/// Identical files are stored once however many entries they are attached to.
+#[tauri::command]
+pub fn attach_file(
+    app: tauri::AppHandle,
+    account_id: String,
+    name: String,
+    data: Vec<u8>,
+) -> Result<AttachmentSummary, CommonError> {
+    let name = attachment_name(&name)?;
+
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let limits = app_state.config.builder().settings.attachments.clone();
+    let history_limits = app_state.config.builder().settings.history.clone();
+
+    app_state
+        .vault_mut()?
+        .update(|db| attach(db, &account_id, name, data, &limits, &history_limits))
+}
+
+/// Export an attached file. Returns its content, or writes it to `path` and returns nothing.
+/// The access is recorded in the audit trail.
+#[tauri::command]
+pub fn export_attachment(
+    app: tauri::AppHandle,
+    account_id: String,
+    name: String,
+    path: Option<PathBuf>,
+) -> Result<Option<Vec<u8>>, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let db = app_state.vault()?.db();
+
+    let entry = find_entry(&db.root, &account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+    let data = attachments(entry)
+        .find(|(n, _)| *n == name)
+        .and_then(|(_, hash)| content(db, hash))
+        .ok_or(CommonError::RequestError(
+            "Attachment not found".to_string(),
+        ))?;
+    audit::record(&app, AuditAction::ExportAttachment, entry, Some(&name))?;
+
+    match path {
+        Some(path) => {
+            write_atomic(&path, data)?;
+            Ok(None)
+        }
+        None => Ok(Some(data.to_vec())),
+    }
+}
+
+/// Remove a file from an account
+#[tauri::command]
+pub fn delete_attachment(
+    app: tauri::AppHandle,
+    account_id: String,
+    name: String,
+) -> Result<(), CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let limits = app_state.config.builder().settings.history.clone();
+
+    app_state
+        .vault_mut()?
+        .update(|db| detach(db, &account_id, &name, &limits))
+}
+
+/// Attaches `data` to the entry as `name`, adding it to the binary pool unless it is there already
+fn attach(
+    db: &mut Database,
+    account_id: &str,
+    name: String,
+    data: Vec<u8>,
+    limits: &AttachmentLimits,
+    history_limits: &HistoryLimits,
+) -> Result<AttachmentSummary, CommonError> {
+    let size = data.len() as u64;
+    if size > limits.max_size {
+        return Err(CommonError::RequestError(format!(
+            "attachment is larger than {} bytes",
+            limits.max_size
+        )));
+    }
+
+    let hash = content_hash(&data);
+    if content(db, &hash).is_none() {
+        let total: u64 = db
+            .header_attachments
+            .iter()
+            .map(|a| a.content.len() as u64)
+            .sum();
+        if total + size > limits.max_total_size {
+            return Err(CommonError::RequestError(format!(
+                "attachments would exceed {} bytes in total",
+                limits.max_total_size
+            )));
+        }
+        db.header_attachments.push(HeaderAttachment {
+            flags: 0,
+            content: data,
+        });
+    }
+
+    let entry = find_entry_mut(&mut db.root, account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+    let version = entry.clone();
+    let previous = entry.custom_data.items.insert(
+        format!("{}{}", ATTACHMENT_PREFIX, name),
+        CustomDataItem {
+            value: Some(Value::Unprotected(hash)),
+            last_modification_time: Some(Times::now()),
+        },
+    );
+    history::record(db, account_id, version, history_limits)?;
+
+    if let Some(previous) = previous.as_ref().and_then(item_hash) {
+        release(db, previous);
+    }
+    Ok(AttachmentSummary { name, size })
+}
+
+/// Removes the attachment `name` from the entry, and its file from the pool once unused
+fn detach(
+    db: &mut Database,
+    account_id: &str,
+    name: &str,
+    limits: &HistoryLimits,
+) -> Result<(), CommonError> {
+    let entry = find_entry_mut(&mut db.root, account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+    let version = entry.clone();
+    let item = entry
+        .custom_data
+        .items
+        .remove(&format!("{}{}", ATTACHMENT_PREFIX, name))
+        .ok_or(CommonError::RequestError(
+            "Attachment not found".to_string(),
+        ))?;
+    history::record(db, account_id, version, limits)?;
+
+    if let Some(hash) = item_hash(&item) {
+        release(db, hash);
+    }
+    Ok(())
+}
+
+/// Attachment names of an entry with the hash of their content
+fn attachments(entry: &Entry) -> impl Iterator<Item = (&str, &str)> {
+    entry
+        .custom_data
+        .items
+        .iter()
+        .filter_map(|(key, item)| Some((key.strip_prefix(ATTACHMENT_PREFIX)?, item_hash(item)?)))
+}
+
+fn item_hash(item: &CustomDataItem) -> Option<&str> {
+    match &item.value {
+        Some(Value::Unprotected(hash)) => Some(hash),
+        _ => None,
+    }
+}
+
+fn content<'a>(db: &'a Database, hash: &str) -> Option<&'a [u8]> {
+    db.header_attachments
+        .iter()
+        .find(|a| content_hash(&a.content) == hash)
+        .map(|a| a.content.as_slice())
+}
+
+fn content_hash(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Drops the files of a removed entry or version from the binary pool, unless something else uses them
+pub(crate) fn release_entry(db: &mut Database, entry: &Entry) {
+    for (_, hash) in attachments(entry) {
+        release(db, hash);
+    }
+}
+
+/// Drops the file with `hash` from the binary pool once no entry, current or in history, uses it
+fn release(db: &mut Database, hash: &str) {
+    if !referenced(&db.root, hash) {
+        db.header_attachments
+            .retain(|a| content_hash(&a.content) != hash);
+    }
+}
+
+fn referenced(group: &Group, hash: &str) -> bool {
+    group.children.iter().any(|node| match node {
+        Node::Group(g) => referenced(g, hash),
+        Node::Entry(e) => std::iter::once(e)
+            .chain(e.history.iter().flat_map(|h| h.get_entries()))
+            .any(|e| attachments(e).any(|(_, h)| h == hash)),
+    })
+}
+
+fn attachment_name(name: &str) -> Result<String, CommonError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CommonError::RequestError(
+            "attachment name is empty".to_string(),
+        ));
+    }
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keepass::config::DatabaseConfig;
+
+    /// History limits that keep no versions, so only the entries themselves hold files
+    const NO_HISTORY: HistoryLimits = HistoryLimits {
+        max_items: 0,
+        max_size: 0,
+    };
+
+    fn database(accounts: usize) -> (Database, Vec<String>) {
+        let mut db = Database::new(DatabaseConfig::default());
+        let ids = (0..accounts)
+            .map(|_| {
+                let entry = Entry::new();
+                let id = entry.uuid.to_string();
+                db.root.children.push(Node::Entry(entry));
+                id
+            })
+            .collect();
+        (db, ids)
+    }
+
+    fn attach_to(
+        db: &mut Database,
+        id: &str,
+        name: &str,
+        data: &[u8],
+        history: &HistoryLimits,
+    ) -> Result<AttachmentSummary, CommonError> {
+        attach(
+            db,
+            id,
+            name.to_string(),
+            data.to_vec(),
+            &AttachmentLimits::default(),
+            history,
+        )
+    }
+
+    fn attached<'a>(db: &'a Database, id: &str, name: &str) -> Option<&'a [u8]> {
+        let entry = find_entry(&db.root, id)?;
+        let (_, hash) = attachments(entry).find(|(n, _)| *n == name)?;
+        content(db, hash)
+    }
+
+    #[test]
+    fn attaches_files_by_name() {
+        let (mut db, ids) = database(1);
+
+        let summary = attach_to(&mut db, &ids[0], "notes.txt", b"notes", &NO_HISTORY).unwrap();
+
+        assert_eq!(summary.name, "notes.txt");
+        assert_eq!(summary.size, 5);
+        assert_eq!(attached(&db, &ids[0], "notes.txt"), Some(&b"notes"[..]));
+        assert!(attach_to(&mut db, "missing", "notes.txt", b"notes", &NO_HISTORY).is_err());
+        assert!(attachment_name("  ").is_err());
+    }
+
+    #[test]
+    fn stores_identical_files_once() {
+        let (mut db, ids) = database(2);
+
+        attach_to(&mut db, &ids[0], "key.pem", b"same", &NO_HISTORY).unwrap();
+        attach_to(&mut db, &ids[1], "copy.pem", b"same", &NO_HISTORY).unwrap();
+        attach_to(&mut db, &ids[1], "other.pem", b"different", &NO_HISTORY).unwrap();
+
+        assert_eq!(db.header_attachments.len(), 2);
+        assert_eq!(attached(&db, &ids[1], "copy.pem"), Some(&b"same"[..]));
+    }
+
+    #[test]
+    fn drops_a_file_once_no_entry_uses_it() {
+        let (mut db, ids) = database(2);
+        attach_to(&mut db, &ids[0], "a", b"shared", &NO_HISTORY).unwrap();
+        attach_to(&mut db, &ids[1], "b", b"shared", &NO_HISTORY).unwrap();
+
+        detach(&mut db, &ids[0], "a", &NO_HISTORY).unwrap();
+        assert_eq!(db.header_attachments.len(), 1);
+        assert!(detach(&mut db, &ids[0], "a", &NO_HISTORY).is_err());
+
+        detach(&mut db, &ids[1], "b", &NO_HISTORY).unwrap();
+        assert!(db.header_attachments.is_empty());
+    }
+
+    #[test]
+    fn keeps_replaced_files_while_history_uses_them() {
+        let (mut db, ids) = database(1);
+        let history = HistoryLimits::default();
+        attach_to(&mut db, &ids[0], "a", b"old", &history).unwrap();
+        attach_to(&mut db, &ids[0], "a", b"new", &history).unwrap();
+
+        assert_eq!(db.header_attachments.len(), 2);
+        assert_eq!(attached(&db, &ids[0], "a"), Some(&b"new"[..]));
+
+        let versions = find_entry_mut(&mut db.root, &ids[0])
+            .unwrap()
+            .history
+            .take()
+            .unwrap();
+        for version in versions.get_entries() {
+            release_entry(&mut db, version);
+        }
+
+        assert_eq!(db.header_attachments.len(), 1);
+        assert_eq!(attached(&db, &ids[0], "a"), Some(&b"new"[..]));
+    }
+
+    #[test]
+    fn enforces_the_size_limits_on_new_content_only() {
+        let (mut db, ids) = database(1);
+        let limits = AttachmentLimits {
+            max_size: 4,
+            max_total_size: 6,
+        };
+        let add = |db: &mut Database, name: &str, data: &[u8]| {
+            attach(
+                db,
+                &ids[0],
+                name.to_string(),
+                data.to_vec(),
+                &limits,
+                &NO_HISTORY,
+            )
+        };
+
+        assert!(add(&mut db, "big", b"12345").is_err());
+        add(&mut db, "a", b"1234").unwrap();
+        assert!(add(&mut db, "b", b"567").is_err());
+        add(&mut db, "c", b"1234").unwrap();
+        assert_eq!(db.header_attachments.len(), 1);
+    }
+}
@@ -0,0 +1,76 @@
+use crate::commands::accounts::entries;
+use crate::state::AppState;
+use crate::utils::app_data_dir::AppDataDir;
+use crate::utils::audit::{self, AuditRecord};
+use keepass::db::Entry;
+use log::info;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use xpassword_entity::{AuditAction, AuditEvent};
+use xpassword_error::CommonError;
+
+/// Events shown when no limit is given
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+
+/// List the most recent secret accesses, newest first, with the titles the accounts have now
+#[tauri::command]
+pub fn list_audit_events(
+    app: tauri::AppHandle,
+    limit: Option<usize>,
+) -> Result<Vec<AuditEvent>, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let db = app_state.vault()?.db();
+    let titles: HashMap<String, String> = entries(&db.root.children)
+        .into_iter()
+        .map(|e| {
+            (
+                e.uuid.to_string(),
+                e.get("Title").unwrap_or_default().to_string(),
+            )
+        })
+        .collect();
+
+    Ok(
+        audit::read(&audit_log(&app), limit.unwrap_or(DEFAULT_AUDIT_LIMIT))?
+            .into_iter()
+            .map(|record| AuditEvent {
+                timestamp: record.timestamp,
+                action: record.action,
+                title: titles.get(&record.account_id).cloned(),
+                account_id: record.account_id,
+                field: record.field,
+            })
+            .collect(),
+    )
+}
+
+/// Records that a secret of `entry` was handed out
+pub(crate) fn record(
+    app: &tauri::AppHandle,
+    action: AuditAction,
+    entry: &Entry,
+    field: Option<&str>,
+) -> Result<(), CommonError> {
+    let record = AuditRecord {
+        timestamp: chrono::Local::now().timestamp() as u64,
+        action,
+        account_id: entry.uuid.to_string(),
+        field: field.map(str::to_string),
+    };
+    info!(
+        "audit: {:?} for account {}",
+        record.action, record.account_id
+    );
+    audit::record(&audit_log(app), &record)
+}
+
+fn audit_log(app: &tauri::AppHandle) -> std::path::PathBuf {
+    AppDataDir::new(
+        app.path()
+            .app_local_data_dir()
+            .expect("could not resolve app local data path"),
+    )
+    .audit_log()
+}
@@ -0,0 +1,229 @@
+use crate::commands::accounts::entries;
+use crate::state::AppState;
+use crate::utils;
+use crate::utils::app_data_dir::AppDataDir;
+use crate::utils::backup::{self, BackupPayload, BACKUP_FORMAT_VERSION};
+use keepass::db::{Entry, Node, Times};
+use keepass::Database;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use xpassword_config::BackupPolicy;
+use xpassword_entity::{
+    BackupImportMode, BackupImportReport, BackupMetadata, LocalBackup, SecretString,
+};
+use xpassword_error::CommonError;
+use xpassword_vault::key_file::database_key;
+use xpassword_vault::{merge_binaries, write_atomic, BackupRotation};
+
+/// Export the vault as an encrypted backup archive.
+/// Returns the archive bytes, or writes them to `path` and returns nothing.
+#[tauri::command]
+pub fn export_backup(
+    app: tauri::AppHandle,
+    password: SecretString,
+    path: Option<PathBuf>,
+) -> Result<Option<Vec<u8>>, CommonError> {
+    utils::validate_password(&password)?;
+
+    let payload = {
+        let state = app.state::<Arc<Mutex<AppState>>>();
+        let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+        let vault = app_state.vault()?;
+        let db = vault.db();
+        let kdbx = vault.to_bytes()?;
+
+        BackupPayload {
+            metadata: BackupMetadata {
+                format_version: BACKUP_FORMAT_VERSION,
+                app_version: app_version(&app),
+                created_at: chrono::Local::now().timestamp() as u64,
+                entry_count: entries(&db.root.children).len(),
+                group_count: group_count(&db.root.children),
+                checksum: backup::checksum(&kdbx),
+            },
+            config: app_state.config.builder().clone(),
+            kdbx,
+        }
+    };
+
+    // Key derivation is deliberately slow, so it runs after the state lock is released
+    let archive = backup::seal(&payload, &password)?;
+
+    match path {
+        Some(path) => {
+            write_atomic(&path, &archive)?;
+            Ok(None)
+        }
+        None => Ok(Some(archive)),
+    }
+}
+
+/// Restore, merge or preview a backup archive produced by `export_backup`.
+/// The vault has to be unlocked. `master_password` opens the backed-up vault, together with
+/// the key file of the current one, and defaults to the current vault key.
+#[tauri::command]
+pub fn import_backup(
+    app: tauri::AppHandle,
+    backup: Vec<u8>,
+    password: SecretString,
+    mode: BackupImportMode,
+    master_password: Option<SecretString>,
+) -> Result<BackupImportReport, CommonError> {
+    // Decrypting runs the slow KDF, so it happens before taking the state lock
+    let payload = backup::open(&backup, &password)?;
+    let mut report = BackupImportReport {
+        metadata: payload.metadata.clone(),
+        added: Vec::new(),
+        changed: Vec::new(),
+        conflicts: Vec::new(),
+    };
+
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let app_data_dir = AppDataDir::new(
+        app.path()
+            .app_local_data_dir()
+            .expect("could not resolve app local data path"),
+    );
+
+    let key_file = app_state.config.builder().key_file().map(Path::to_path_buf);
+    let vault = app_state.vault_mut()?;
+    let backup_key = match master_password {
+        Some(password) => database_key(&password, key_file.as_deref())?,
+        None => vault.key().clone(),
+    };
+    let other = Database::open(&mut payload.kdbx.as_slice(), backup_key)
+        .map_err(|_| CommonError::InvalidMasterKey)?;
+
+    if mode == BackupImportMode::Replace {
+        // The file being replaced is copied into the backups first, even when they are turned off.
+        // The restored vault takes the key of the one it replaces, so it stays unlocked.
+        vault.restore(other)?;
+
+        // Settings come from the backup, but the vault and key files stay where this install
+        // keeps them
+        let builder = app_state.config.builder();
+        let config = xpassword_config::Builder {
+            kdbx_path: builder.kdbx_path.clone(),
+            key_files: builder.key_files.clone(),
+            ..payload.config
+        };
+        let backups = backup_rotation(&app_data_dir, &config.settings.backup);
+        app_state.config.set_builder(config).store();
+        app_state.vault_mut()?.set_backups(Some(backups));
+
+        return Ok(report);
+    }
+
+    diff(vault.db(), &other, &mut report);
+
+    if mode == BackupImportMode::Merge {
+        vault.update(|db| {
+            db.merge(&other).map_err(|e| {
+                CommonError::UnexpectedError(anyhow::anyhow!("Failed to merge backup: {}", e))
+            })?;
+            merge_binaries(db, &other);
+            Ok(())
+        })?;
+    }
+
+    Ok(report)
+}
+
+/// Automatic backups of the vault, newest first
+#[tauri::command]
+pub fn list_local_backups(app: tauri::AppHandle) -> Result<Vec<LocalBackup>, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let vault = app_state.vault()?;
+
+    match vault.backups() {
+        Some(backups) => backups.list(vault.path()),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Replace the vault with one of its automatic backups.
+/// The file being replaced is itself backed up first, even when backups are turned off.
+#[tauri::command]
+pub fn restore_local_backup(app: tauri::AppHandle, name: String) -> Result<(), CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let vault = app_state.vault_mut()?;
+
+    let source = vault
+        .backups()
+        .ok_or(CommonError::RequestError(
+            "backups are not enabled".to_string(),
+        ))?
+        .resolve(vault.path(), &name)?;
+    vault.restore_from(&source)
+}
+
+/// Backup rotation for `policy`, kept under the app data dir unless it names a directory
+pub(crate) fn backup_rotation(app_data_dir: &AppDataDir, policy: &BackupPolicy) -> BackupRotation {
+    let dir = if policy.dir.as_os_str().is_empty() {
+        app_data_dir.backups()
+    } else {
+        policy.dir.clone()
+    };
+    BackupRotation::new(dir, policy.clone())
+}
+
+/// Classifies backup entries the way a merge treats them: new ones are added, and of the
+/// ones that differ the newer side wins while the older version goes into history
+fn diff(db: &Database, other: &Database, report: &mut BackupImportReport) {
+    let current: HashMap<_, _> = entries(&db.root.children)
+        .into_iter()
+        .map(|e| (e.uuid, e))
+        .collect();
+
+    for entry in entries(&other.root.children) {
+        let name = entry.get("Title").unwrap_or_default().to_string();
+        match current.get(&entry.uuid) {
+            None if !db.deleted_objects.contains(entry.uuid) => report.added.push(name),
+            None => {}
+            Some(existing) if differs(existing, &entry) => {
+                if entry.times.get_last_modification() > existing.times.get_last_modification() {
+                    report.changed.push(name);
+                } else {
+                    report.conflicts.push(name);
+                }
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+fn differs(a: &Entry, b: &Entry) -> bool {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    a.times = Times::default();
+    b.times = Times::default();
+    a != b
+}
+
+/// Version recorded in `version.txt`, falling back to the running app's version
+fn app_version(app: &tauri::AppHandle) -> String {
+    let app_data_dir = AppDataDir::new(
+        app.path()
+            .app_local_data_dir()
+            .expect("could not resolve app local data path"),
+    );
+
+    fs::read_to_string(app_data_dir.version())
+        .map(|v| v.trim().to_string())
+        .unwrap_or_else(|_| format!("v{}", app.config().version.clone().unwrap_or_default()))
+}
+
+fn group_count(nodes: &[Node]) -> usize {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::Group(g) => 1 + group_count(&g.children),
+            Node::Entry(_) => 0,
+        })
+        .sum()
+}
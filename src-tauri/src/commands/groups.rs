@@ -0,0 +1,307 @@
+use crate::commands::recycle_bin::{self, recycle_bin};
+use crate::state::AppState;
+use keepass::db::{Entry, Group, Node, Times};
+use keepass::Database;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use xpassword_entity::{CreateGroupRequest, GroupSummary};
+use xpassword_error::CommonError;
+
+/// List the group tree below the root group
+#[tauri::command]
+pub async fn list_groups(app: tauri::AppHandle) -> Result<Vec<GroupSummary>, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    let db = app_state.vault()?.db();
+    let bin = recycle_bin(db).map(|bin| bin.uuid);
+    Ok(group_summaries(&db.root, None, bin))
+}
+
+/// Create a group, under the root group unless a parent is given.
+/// Returns the id of the new group.
+#[tauri::command]
+pub fn create_group(
+    app: tauri::AppHandle,
+    request: CreateGroupRequest,
+) -> Result<String, CommonError> {
+    let name = group_name(&request.name)?;
+
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    app_state.vault_mut()?.update(|db| {
+        let group = Group::new(&name);
+        let id = group.uuid.to_string();
+        target_group(db, request.parent_id.as_deref())?
+            .children
+            .push(Node::Group(group));
+        Ok(id)
+    })
+}
+
+/// Rename a group
+#[tauri::command]
+pub fn rename_group(
+    app: tauri::AppHandle,
+    group_id: String,
+    name: String,
+) -> Result<(), CommonError> {
+    let name = group_name(&name)?;
+
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    app_state.vault_mut()?.update(|db| {
+        let group = find_group_mut(&mut db.root, &group_id)
+            .ok_or(CommonError::RequestError("Group not found".to_string()))?;
+        group.name = name;
+        group.times.set_last_modification(Times::now());
+        Ok(())
+    })
+}
+
+/// Move a group under another parent, or to another position among its siblings.
+/// `index` counts sibling groups only; without it the group goes after the last one.
+#[tauri::command]
+pub fn move_group(
+    app: tauri::AppHandle,
+    group_id: String,
+    parent_id: Option<String>,
+    index: Option<usize>,
+) -> Result<(), CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    app_state.vault_mut()?.update(|db| {
+        if db.root.uuid.to_string() == group_id {
+            return Err(CommonError::RequestError(
+                "The root group cannot be moved".to_string(),
+            ));
+        }
+        let group = find_group(&db.root, &group_id)
+            .ok_or(CommonError::RequestError("Group not found".to_string()))?;
+        if let Some(parent_id) = parent_id.as_deref() {
+            if find_group(group, parent_id).is_some() {
+                return Err(CommonError::RequestError(
+                    "A group cannot be moved into itself".to_string(),
+                ));
+            }
+        }
+        // Fail before detaching anything when the destination does not exist
+        target_group(db, parent_id.as_deref())?;
+
+        let Some(Node::Group(mut group)) = take_node(
+            &mut db.root,
+            &|node| matches!(node, Node::Group(g) if g.uuid.to_string() == group_id),
+        ) else {
+            return Err(CommonError::RequestError("Group not found".to_string()));
+        };
+        group.times.set_location_changed(Times::now());
+
+        let parent = target_group(db, parent_id.as_deref())?;
+        let position = parent
+            .children
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node, Node::Group(_)))
+            .nth(index.unwrap_or(usize::MAX))
+            .map(|(i, _)| i)
+            .unwrap_or(parent.children.len());
+        parent.children.insert(position, Node::Group(group));
+        Ok(())
+    })
+}
+
+/// Delete a group into the recycle bin, or for good when it is already there or the bin is
+/// disabled. Unless `recursive` is set, only empty groups can be deleted.
+#[tauri::command]
+pub fn delete_group(
+    app: tauri::AppHandle,
+    group_id: String,
+    recursive: bool,
+) -> Result<(), CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    app_state.vault_mut()?.update(|db| {
+        if db.root.uuid.to_string() == group_id {
+            return Err(CommonError::RequestError(
+                "The root group cannot be deleted".to_string(),
+            ));
+        }
+        let group = find_group(&db.root, &group_id)
+            .ok_or(CommonError::RequestError("Group not found".to_string()))?;
+        if !recursive && !group.children.is_empty() {
+            return Err(CommonError::RequestError("Group is not empty".to_string()));
+        }
+
+        recycle_bin::delete_node(
+            db,
+            &|node| matches!(node, Node::Group(g) if g.uuid.to_string() == group_id),
+        )?;
+        Ok(())
+    })
+}
+
+/// Move an account into a group, or into the root group when none is given
+#[tauri::command]
+pub fn move_account(
+    app: tauri::AppHandle,
+    account_id: String,
+    group_id: Option<String>,
+) -> Result<(), CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    app_state
+        .vault_mut()?
+        .update(|db| move_entry(db, &account_id, group_id.as_deref()))
+}
+
+/// Moves an entry into the group `group_id` (the root group when `None`),
+/// leaving it in place if it is already there
+pub(crate) fn move_entry(
+    db: &mut Database,
+    account_id: &str,
+    group_id: Option<&str>,
+) -> Result<(), CommonError> {
+    let target = target_group(db, group_id)?;
+    if target
+        .children
+        .iter()
+        .any(|node| matches!(node, Node::Entry(e) if e.uuid.to_string() == account_id))
+    {
+        return Ok(());
+    }
+
+    let Some(Node::Entry(mut entry)) = take_node(
+        &mut db.root,
+        &|node| matches!(node, Node::Entry(e) if e.uuid.to_string() == account_id),
+    ) else {
+        return Err(CommonError::RequestError("Account not found".to_string()));
+    };
+    entry.times.set_location_changed(Times::now());
+
+    target_group(db, group_id)?
+        .children
+        .push(Node::Entry(entry));
+    Ok(())
+}
+
+/// Adds a new entry to the group `group_id`, or to the root group when `None`
+pub(crate) fn add_entry(
+    db: &mut Database,
+    entry: Entry,
+    group_id: Option<&str>,
+) -> Result<(), CommonError> {
+    target_group(db, group_id)?
+        .children
+        .push(Node::Entry(entry));
+    Ok(())
+}
+
+fn group_summaries(
+    parent: &Group,
+    parent_id: Option<String>,
+    bin: Option<uuid::Uuid>,
+) -> Vec<GroupSummary> {
+    parent
+        .children
+        .iter()
+        .filter_map(|node| match node {
+            Node::Group(g) => Some(group_summary(g, parent_id.clone(), bin)),
+            Node::Entry(_) => None,
+        })
+        .collect()
+}
+
+fn group_summary(
+    group: &Group,
+    parent_id: Option<String>,
+    bin: Option<uuid::Uuid>,
+) -> GroupSummary {
+    let children = group_summaries(group, Some(group.uuid.to_string()), bin);
+    let entry_count = group
+        .children
+        .iter()
+        .filter(|node| matches!(node, Node::Entry(_)))
+        .count();
+
+    GroupSummary {
+        id: group.uuid.to_string(),
+        name: group.name.clone(),
+        icon_id: group.icon_id,
+        custom_icon_id: group.custom_icon_uuid.map(|uuid| uuid.to_string()),
+        parent_id,
+        entry_count,
+        total_entry_count: entry_count
+            + children.iter().map(|c| c.total_entry_count).sum::<usize>(),
+        expanded: group.is_expanded,
+        recycle_bin: Some(group.uuid) == bin,
+        children,
+    }
+}
+
+/// The group `group_id`, or the root group when `None`
+pub(crate) fn target_group<'a>(
+    db: &'a mut Database,
+    group_id: Option<&str>,
+) -> Result<&'a mut Group, CommonError> {
+    match group_id {
+        Some(id) => find_group_mut(&mut db.root, id)
+            .ok_or(CommonError::RequestError("Group not found".to_string())),
+        None => Ok(&mut db.root),
+    }
+}
+
+pub(crate) fn find_group<'a>(group: &'a Group, id: &str) -> Option<&'a Group> {
+    if group.uuid.to_string() == id {
+        return Some(group);
+    }
+    for node in &group.children {
+        if let Node::Group(g) = node {
+            if let Some(found) = find_group(g, id) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+fn find_group_mut<'a>(group: &'a mut Group, id: &str) -> Option<&'a mut Group> {
+    if group.uuid.to_string() == id {
+        return Some(group);
+    }
+    for node in &mut group.children {
+        if let Node::Group(g) = node {
+            if let Some(found) = find_group_mut(g, id) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+/// Detaches the first node matching `is_match` from anywhere under `group`
+pub(crate) fn take_node(group: &mut Group, is_match: &impl Fn(&Node) -> bool) -> Option<Node> {
+    if let Some(index) = group.children.iter().position(is_match) {
+        return Some(group.children.remove(index));
+    }
+    for node in &mut group.children {
+        if let Node::Group(g) = node {
+            if let Some(found) = take_node(g, is_match) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+fn group_name(name: &str) -> Result<String, CommonError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CommonError::RequestError("group name is empty".to_string()));
+    }
+    Ok(name.to_string())
+}
@@ -0,0 +1,45 @@
+use crate::commands::recycle_bin::active_entries;
+use crate::state::AppState;
+use crate::utils::recovery_codes::{self, LOW_RECOVERY_CODES};
+use crate::utils::search::{has_weak_password, is_expired};
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use xpassword_entity::{HealthIssue, HealthReport, HealthWarning};
+use xpassword_error::CommonError;
+
+/// Check every account for weak passwords, expiry and recovery codes running low
+#[tauri::command]
+pub async fn health_report(app: tauri::AppHandle) -> Result<HealthReport, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    let entries = active_entries(app_state.vault()?.db());
+    let mut warnings = Vec::new();
+    for entry in &entries {
+        let mut issues = Vec::new();
+        if has_weak_password(entry) {
+            issues.push(HealthIssue::WeakPassword);
+        }
+        if is_expired(entry) {
+            issues.push(HealthIssue::Expired);
+        }
+        if let Some(count) = recovery_codes::count(entry) {
+            if count.remaining <= LOW_RECOVERY_CODES {
+                issues.push(HealthIssue::LowRecoveryCodes {
+                    remaining: count.remaining,
+                });
+            }
+        }
+
+        warnings.extend(issues.into_iter().map(|issue| HealthWarning {
+            account_id: entry.uuid.to_string(),
+            title: entry.get("Title").unwrap_or_default().to_string(),
+            issue,
+        }));
+    }
+
+    Ok(HealthReport {
+        accounts: entries.len(),
+        warnings,
+    })
+}
@@ -0,0 +1,228 @@
+use crate::commands::accounts::{find_entry, find_entry_mut};
+use crate::commands::attachments::release_entry;
+use crate::state::AppState;
+use keepass::db::{Entry, History, Times, Value};
+use keepass::Database;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use xpassword_config::HistoryLimits;
+use xpassword_entity::{EntryVersion, FieldChange};
+use xpassword_error::CommonError;
+
+/// List the previous versions of an account, most recent first
+#[tauri::command]
+pub async fn list_versions(
+    app: tauri::AppHandle,
+    account_id: String,
+) -> Result<Vec<EntryVersion>, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    let entry = find_entry(&app_state.vault()?.db().root, &account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+    Ok(versions(entry)
+        .iter()
+        .enumerate()
+        .map(|(index, version)| EntryVersion {
+            index,
+            modified_at: version
+                .times
+                .get_last_modification()
+                .map(|t| t.and_utc().timestamp() as u64),
+            title: version.get("Title").unwrap_or_default().to_string(),
+        })
+        .collect())
+}
+
+/// Fields that differ between two versions of an account.
+/// Versions are history indexes as listed by `list_versions`; absent means the current version.
+#[tauri::command]
+pub async fn diff_versions(
+    app: tauri::AppHandle,
+    account_id: String,
+    from: Option<usize>,
+    to: Option<usize>,
+) -> Result<Vec<FieldChange>, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    let entry = find_entry(&app_state.vault()?.db().root, &account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+    Ok(diff(version(entry, from)?, version(entry, to)?))
+}
+
+/// Restore a previous version of an account. The current version goes into the history
+/// like any other edit, so the restore can be undone.
+#[tauri::command]
+pub fn restore_version(
+    app: tauri::AppHandle,
+    account_id: String,
+    index: usize,
+) -> Result<(), CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let limits = app_state.config.builder().settings.history.clone();
+
+    app_state.vault_mut()?.update(|db| {
+        let entry = find_entry_mut(&mut db.root, &account_id)
+            .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+        let previous = entry.clone();
+        let mut restored = version(entry, Some(index))?.clone();
+
+        // The version keeps its expiry, but not its other timestamps
+        let mut times = entry.times.clone();
+        times.expires = restored.times.expires;
+        if let Some(expiry) = restored.times.get_expiry() {
+            times.set_expiry(*expiry);
+        }
+        restored.times = times;
+        restored.history = entry.history.take();
+        *entry = restored;
+
+        record(db, &account_id, previous, &limits)
+    })
+}
+
+/// Adds `previous`, the state of an entry before an edit, to its history if the edit changed
+/// anything, then drops the oldest versions beyond `limits`
+pub(crate) fn record(
+    db: &mut Database,
+    account_id: &str,
+    mut previous: Entry,
+    limits: &HistoryLimits,
+) -> Result<(), CommonError> {
+    let entry = find_entry_mut(&mut db.root, account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+    if !changed(&previous, entry) {
+        return Ok(());
+    }
+    entry.times.set_last_modification(Times::now());
+
+    previous.history = None;
+    let mut kept = versions(entry).to_vec();
+    kept.insert(0, previous);
+
+    let mut total = 0;
+    let keep = kept
+        .iter()
+        .take(limits.max_items)
+        .take_while(|version| {
+            total += size(version);
+            total <= limits.max_size
+        })
+        .count();
+    let dropped = kept.split_off(keep);
+
+    // `History::add_entry` puts each version in front, so they are added oldest first
+    let mut history = History::default();
+    for version in kept.into_iter().rev() {
+        history.add_entry(version);
+    }
+    entry.history = Some(history);
+
+    for version in &dropped {
+        release_entry(db, version);
+    }
+    Ok(())
+}
+
+/// Previous versions of an entry, most recent first
+fn versions(entry: &Entry) -> &[Entry] {
+    entry
+        .history
+        .as_ref()
+        .map(|history| history.get_entries().as_slice())
+        .unwrap_or_default()
+}
+
+/// The history version at `index`, or the entry itself when `None`
+fn version(entry: &Entry, index: Option<usize>) -> Result<&Entry, CommonError> {
+    match index {
+        Some(index) => versions(entry)
+            .get(index)
+            .ok_or(CommonError::RequestError("Version not found".to_string())),
+        None => Ok(entry),
+    }
+}
+
+fn changed(a: &Entry, b: &Entry) -> bool {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    a.times = Times::default();
+    b.times = Times::default();
+    a.history = None;
+    b.history = None;
+    a != b
+}
+
+/// Approximate size of a version: the text of its fields, tags and custom data
+fn size(entry: &Entry) -> u64 {
+    let fields: usize = entry
+        .fields
+        .iter()
+        .map(|(key, value)| key.len() + value_bytes(value).len())
+        .sum();
+    let tags: usize = entry.tags.iter().map(String::len).sum();
+    let custom_data: usize = entry
+        .custom_data
+        .items
+        .iter()
+        .map(|(key, item)| key.len() + item.value.as_ref().map_or(0, |v| value_bytes(v).len()))
+        .sum();
+    (fields + tags + custom_data) as u64
+}
+
+fn value_bytes(value: &Value) -> &[u8] {
+    match value {
+        Value::Unprotected(value) => value.as_bytes(),
+        Value::Protected(value) => value.unsecure(),
+        Value::Bytes(value) => value,
+    }
+}
+
+/// Changed string fields and tags between two versions, by name.
+/// Values of fields protected in either version are left out.
+fn diff(from: &Entry, to: &Entry) -> Vec<FieldChange> {
+    let keys: BTreeSet<&String> = from
+        .fields
+        .keys()
+        .chain(to.fields.keys())
+        .filter(|key| {
+            !matches!(from.fields.get(*key), Some(Value::Bytes(_)))
+                && !matches!(to.fields.get(*key), Some(Value::Bytes(_)))
+        })
+        .collect();
+
+    let mut changes: Vec<FieldChange> = keys
+        .into_iter()
+        .filter(|key| from.fields.get(*key) != to.fields.get(*key))
+        .map(|key| {
+            let (old, new) = (from.fields.get(key), to.fields.get(key));
+            let protected = [old, new]
+                .iter()
+                .any(|value| matches!(value, Some(Value::Protected(_))));
+            let shown = |value: Option<&Value>| {
+                value.map(|value| match value {
+                    Value::Unprotected(value) if !protected => value.clone(),
+                    _ => String::new(),
+                })
+            };
+            FieldChange {
+                field: key.clone(),
+                old: shown(old),
+                new: shown(new),
+                protected,
+            }
+        })
+        .collect();
+
+    if from.tags != to.tags {
+        changes.push(FieldChange {
+            field: "Tags".to_string(),
+            old: Some(from.tags.join(", ")),
+            new: Some(to.tags.join(", ")),
+            protected: false,
+        });
+    }
+    changes
+}
@@ -0,0 +1,283 @@
+use crate::commands::accounts::new_entry;
+use crate::commands::recycle_bin::active_entries;
+use crate::importers::{importer, ImportBatch};
+use crate::state::AppState;
+use keepass::db::{Group, Icon, Node};
+use keepass::Database;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use uuid::Uuid;
+use xpassword_entity::{
+    ImportPreview, ImportReport, ImportSource, OtpParams, OtpType, SecretString, UnsupportedEntry,
+};
+use xpassword_error::CommonError;
+
+/// Import accounts from any supported authenticator export
+#[tauri::command]
+pub async fn import_accounts(
+    app: tauri::AppHandle,
+    source: ImportSource,
+    data: Vec<u8>,
+    password: Option<SecretString>,
+) -> Result<ImportReport, CommonError> {
+    let batch = importer(source).decode(&data, password.as_deref())?;
+    import_batch(&app, batch, false)
+}
+
+/// Decode an export and report what importing it would do, without saving anything
+#[tauri::command]
+pub async fn preview_import(
+    app: tauri::AppHandle,
+    source: ImportSource,
+    data: Vec<u8>,
+    password: Option<SecretString>,
+) -> Result<ImportPreview, CommonError> {
+    let batch = importer(source).decode(&data, password.as_deref())?;
+    let report = import_batch(&app, batch, true)?;
+
+    Ok(ImportPreview {
+        count: report.imported,
+        conflicts: report.duplicates,
+        unsupported: report.unsupported,
+    })
+}
+
+/// Insert decoded accounts, skipping any whose OTP secret is already in the vault outside
+/// the recycle bin.
+/// With `dry_run` the report is computed the same way but nothing is inserted.
+fn import_batch(
+    app: &tauri::AppHandle,
+    batch: ImportBatch,
+    dry_run: bool,
+) -> Result<ImportReport, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    let vault = app_state.vault_mut()?;
+    // Work on a copy that only replaces the open vault once it has been saved
+    let mut db = vault.db().clone();
+    let report = add_accounts(&mut db, batch, dry_run);
+
+    if report.imported > 0 && !dry_run {
+        vault.replace(db)?;
+    }
+
+    Ok(report)
+}
+
+/// Adds the accounts of `batch` to `db` and reports what happened to each of them
+fn add_accounts(db: &mut Database, batch: ImportBatch, dry_run: bool) -> ImportReport {
+    let mut known: HashSet<(OtpType, String)> = active_entries(db)
+        .iter()
+        .filter_map(|e| otp_key(e.get("TOTP")?))
+        .collect();
+
+    let mut report = ImportReport {
+        unsupported: batch.unsupported,
+        ..Default::default()
+    };
+
+    for account in batch.accounts {
+        let request = account.request;
+        let name = if request.username.is_empty() || request.title == request.username {
+            request.title.clone()
+        } else {
+            format!("{} ({})", request.title, request.username)
+        };
+
+        let mut entry = match new_entry(request) {
+            Ok(entry) => entry,
+            Err(e) => {
+                report.unsupported.push(UnsupportedEntry {
+                    name,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        if let Some(key) = entry.get("TOTP").and_then(otp_key) {
+            if !known.insert(key) {
+                report.duplicates.push(name);
+                continue;
+            }
+        }
+
+        report.imported += 1;
+        if dry_run {
+            continue;
+        }
+
+        if let Some(data) = account.icon {
+            let uuid = Uuid::now_v7();
+            db.meta.custom_icons.icons.push(Icon { uuid, data });
+            entry.custom_icon_uuid = Some(uuid);
+        }
+
+        match account.group {
+            Some(name) => group_mut(&mut db.root, &name)
+                .children
+                .push(Node::Entry(entry)),
+            None => db.root.children.push(Node::Entry(entry)),
+        }
+    }
+
+    report
+}
+
+/// Finds a direct subgroup by name, creating it when missing
+fn group_mut<'a>(parent: &'a mut Group, name: &str) -> &'a mut Group {
+    let index = parent
+        .children
+        .iter()
+        .position(|node| matches!(node, Node::Group(g) if g.name == name));
+    let index = match index {
+        Some(index) => index,
+        None => {
+            parent.children.push(Node::Group(Group::new(name)));
+            parent.children.len() - 1
+        }
+    };
+
+    match &mut parent.children[index] {
+        Node::Group(group) => group,
+        Node::Entry(_) => unreachable!(),
+    }
+}
+
+fn otp_key(value: &str) -> Option<(OtpType, String)> {
+    let otp: OtpParams = value.parse().ok()?;
+    Some((otp.otp_type, otp.secret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::accounts::entries;
+    use keepass::config::DatabaseConfig;
+    use xpassword_entity::CreateAccountRequest;
+
+    const PASSWORD: Option<&str> = Some("hunter2");
+
+    /// Every fixture holds a TOTP account for "ACME Co", an HOTP account for "bob"
+    /// and one account the importers cannot take
+    fn fixtures() -> Vec<(ImportSource, &'static [u8], Option<&'static str>)> {
+        vec![
+            (
+                ImportSource::Aegis,
+                include_bytes!("../../tests/fixtures/aegis_plain.json"),
+                None,
+            ),
+            (
+                ImportSource::Aegis,
+                include_bytes!("../../tests/fixtures/aegis_encrypted.json"),
+                PASSWORD,
+            ),
+            (
+                ImportSource::AndOtp,
+                include_bytes!("../../tests/fixtures/and_otp_plain.json"),
+                None,
+            ),
+            (
+                ImportSource::AndOtp,
+                include_bytes!("../../tests/fixtures/and_otp_pbkdf2.json.aes"),
+                PASSWORD,
+            ),
+            (
+                ImportSource::AndOtp,
+                include_bytes!("../../tests/fixtures/and_otp_legacy.json.aes"),
+                PASSWORD,
+            ),
+            (
+                ImportSource::TwoFas,
+                include_bytes!("../../tests/fixtures/two_fas_plain.2fas"),
+                None,
+            ),
+            (
+                ImportSource::TwoFas,
+                include_bytes!("../../tests/fixtures/two_fas_encrypted.2fas"),
+                PASSWORD,
+            ),
+            (
+                ImportSource::FreeOtpPlus,
+                include_bytes!("../../tests/fixtures/free_otp_plus.json"),
+                None,
+            ),
+        ]
+    }
+
+    /// A vault that already has the "ACME Co" secret
+    fn vault() -> Database {
+        let mut db = Database::new(DatabaseConfig::default());
+        let entry = new_entry(CreateAccountRequest {
+            title: "ACME".to_string(),
+            username: String::new(),
+            password: SecretString::default(),
+            url: String::new(),
+            notes: String::new(),
+            notes_protected: false,
+            totp: Some("JBSWY3DPEHPK3PXP".into()),
+            tags: Vec::new(),
+            custom_fields: Vec::new(),
+            recovery_codes: Vec::new(),
+            group_id: None,
+        })
+        .unwrap();
+        db.root.children.push(Node::Entry(entry));
+        db
+    }
+
+    #[test]
+    fn previews_every_fixture_without_changing_the_vault() {
+        for (source, data, password) in fixtures() {
+            let batch = importer(source).decode(data, password).unwrap();
+            let mut db = vault();
+            let report = add_accounts(&mut db, batch, true);
+
+            assert_eq!(report.imported, 1, "{:?}", source);
+            assert_eq!(
+                report.duplicates,
+                ["ACME Co (john@example.com)"],
+                "{:?}",
+                source
+            );
+            assert_eq!(report.unsupported.len(), 1, "{:?}", source);
+            assert_eq!(entries(&db.root.children).len(), 1);
+        }
+    }
+
+    #[test]
+    fn imports_into_named_groups() {
+        let (source, data, password) = fixtures().swap_remove(0);
+        let batch = importer(source).decode(data, password).unwrap();
+        let mut db = Database::new(DatabaseConfig::default());
+        let report = add_accounts(&mut db, batch, false);
+
+        assert_eq!(report.imported, 2);
+        let work = db
+            .root
+            .children
+            .iter()
+            .find_map(|node| match node {
+                Node::Group(g) if g.name == "Work" => Some(g),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(entries(&work.children).len(), 1);
+        assert_eq!(db.meta.custom_icons.icons.len(), 1);
+    }
+
+    #[test]
+    fn rejects_wrong_passwords() {
+        for (source, data, password) in fixtures() {
+            if password.is_some() {
+                assert!(
+                    importer(source).decode(data, Some("hunter3")).is_err(),
+                    "{:?}",
+                    source
+                );
+            }
+        }
+    }
+}
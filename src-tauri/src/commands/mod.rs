@@ -1,17 +1,31 @@
+use crate::constants::app_events::AppEvents;
+use crate::state::auto_lock;
 use crate::state::AppState;
 use crate::utils;
 use crate::utils::app_data_dir::AppDataDir;
 use keepass::config::DatabaseConfig;
-use keepass::{Database, DatabaseKey};
+use keepass::Database;
 use log::{debug, info};
 use std::fs;
-use std::fs::File;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use tauri::Manager;
-use xpassword_entity::{AppDefault, InitRequest};
+use tauri::{Emitter, Manager};
+use xpassword_entity::{AppDefault, InitRequest, LockReason, SecretString};
 use xpassword_error::CommonError;
+use xpassword_vault::key_file::{self, database_key};
+use xpassword_vault::VaultStore;
 
 pub mod accounts;
+pub mod attachments;
+pub mod audit;
+pub mod backup;
+pub mod groups;
+pub mod health;
+pub mod history;
+pub mod import;
+pub mod recovery_codes;
+pub mod recycle_bin;
+pub mod tags;
 
 #[tauri::command]
 pub fn app_default(app: tauri::AppHandle) -> Result<AppDefault, CommonError> {
@@ -28,7 +42,7 @@ pub fn app_default(app: tauri::AppHandle) -> Result<AppDefault, CommonError> {
 
 #[tauri::command]
 pub fn init_app(app: tauri::AppHandle, request: InitRequest) -> Result<(), CommonError> {
-    debug!("Initializing app request:{:?}", request);
+    debug!("Initializing app at {:?}", request.kdbx_path);
     let state = app.state::<Arc<Mutex<AppState>>>();
     let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
     let app_data_dir = AppDataDir::new(
@@ -39,29 +53,66 @@ pub fn init_app(app: tauri::AppHandle, request: InitRequest) -> Result<(), Commo
 
     let mut db = Database::new(DatabaseConfig::default());
     db.meta.database_name = Some("Accounts Database".to_string());
+    db.meta.recyclebin_enabled = Some(true);
 
     // Validate password strength
     utils::validate_password(&request.password)?;
     let kdbx_path = request.kdbx_path.clone();
-    if !request.kdbx_path.exists() {
-        db.save(
-            &mut File::create(request.kdbx_path).expect("could not create kdbx file"),
-            DatabaseKey::new().with_password(request.password.as_str()),
-        )
-        .expect("could not save kdbx");
-    }
+    let key_file = match (request.key_file.clone(), request.generate_key_file) {
+        (_, true) if kdbx_path.exists() => {
+            return Err(CommonError::RequestError(
+                "a key file can only be generated for a new vault".to_string(),
+            ))
+        }
+        (key_file, true) => {
+            let key_file = key_file.unwrap_or_else(|| kdbx_path.with_extension("keyx"));
+            key_file::generate(&key_file)?;
+            Some(key_file)
+        }
+        (key_file, false) => key_file,
+    };
+    let opened = database_key(&request.password, key_file.as_deref()).and_then(|key| {
+        if kdbx_path.exists() {
+            VaultStore::open(kdbx_path.clone(), key)
+        } else {
+            VaultStore::create(kdbx_path.clone(), db, key)
+        }
+    });
+    let mut vault = match opened {
+        Ok(vault) => vault,
+        Err(e) => {
+            // A generated key file is useless without the vault it was made for
+            if let (true, Some(key_file)) = (request.generate_key_file, &key_file) {
+                let _ = fs::remove_file(key_file);
+            }
+            return Err(e);
+        }
+    };
+    notify_external_merges(&app, &mut vault);
 
     let mut config = xpassword_config::Config::init(app_data_dir.config()).load();
     config
-        .set_builder(config.builder().clone().set_kdbx_path(kdbx_path))
+        .set_builder(
+            config
+                .builder()
+                .clone()
+                .set_kdbx_path(kdbx_path)
+                .set_key_file(key_file),
+        )
         .store();
+    vault.set_backups(Some(backup::backup_rotation(
+        &app_data_dir,
+        &config.builder().settings.backup,
+    )));
+    recycle_bin::purge_expired(&mut vault, config.builder().settings.recycle_bin_purge_days)?;
 
     app_state.config = config;
     app_state.is_initialized = true;
     app_state.is_locked = false;
     app_state.runtime_timestamp = chrono::Local::now().timestamp() as u64;
-    app_state.db = Some(db);
-    app_state.master_password = Some(request.password);
+    app_state.vault = Some(vault);
+    app_state.touch();
+    app_state.search_index()?;
 
     info!("app initialized");
 
@@ -142,78 +193,112 @@ pub fn launch_app(app: tauri::AppHandle) -> Result<(), CommonError> {
 #[tauri::command]
 pub fn app_state(app: tauri::AppHandle) -> Result<AppState, CommonError> {
     let state = app.state::<Arc<Mutex<AppState>>>();
-    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
 
-    let settings = app_state.config.builder().settings.clone();
-    let timeout = settings.auto_lock_timeout;
-    let auto_lock_enabled = settings.auto_lock;
-
-    if auto_lock_enabled && !app_state.is_locked {
-        let now = chrono::Local::now().timestamp() as u64;
-        if now.saturating_sub(app_state.runtime_timestamp) >= timeout {
-            app_state.is_locked = true;
-            app_state.locked_timestamp = Some(now);
-            app_state.db = None;
-            return Err(CommonError::AppIsLocked);
-        }
-    }
-
-    Ok(app_state.clone())
+    Ok(app_state.snapshot())
 }
 
+/// Unlock the vault with the master password and its key file, if it uses one.
+/// A `key_file` given here is remembered for the vault; when absent the remembered one is used.
 #[tauri::command]
-pub fn unlock_with_password(app: tauri::AppHandle, password: String) -> Result<(), CommonError> {
+pub fn unlock_with_password(
+    app: tauri::AppHandle,
+    password: SecretString,
+    key_file: Option<PathBuf>,
+) -> Result<(), CommonError> {
     if password.is_empty() {
         return Err(CommonError::RequestError("password is empty".to_string()));
     }
 
     let state = app.state::<Arc<Mutex<AppState>>>();
     let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let app_data_dir = AppDataDir::new(
+        app.path()
+            .app_local_data_dir()
+            .expect("could not resolve app local data path"),
+    );
 
     let kdbx_path = app_state.config.builder().kdbx_path.clone();
 
     // Try to open the database with the provided password
-    if !kdbx_path.exists() {
-        return Err(CommonError::KdbxNotInitialized);
+    let remembered = app_state.config.builder().key_file().map(Path::to_path_buf);
+    let key = database_key(&password, key_file.as_deref().or(remembered.as_deref()))?;
+    let mut vault = VaultStore::open(kdbx_path, key)?;
+    if key_file.is_some() && key_file != remembered {
+        let builder = app_state.config.builder().clone().set_key_file(key_file);
+        app_state.config.set_builder(builder).store();
     }
-
-    let mut file = File::open(&kdbx_path).map_err(|e| {
-        CommonError::UnexpectedError(anyhow::anyhow!("Failed to open KDBX file: {}", e))
-    })?;
-
-    let db = Database::open(&mut file, DatabaseKey::new().with_password(&password))
-        .map_err(|_| CommonError::InvalidPassword)?;
-
-    app_state.db = Some(db);
-    app_state.master_password = Some(password);
+    notify_external_merges(&app, &mut vault);
+    vault.set_backups(Some(backup::backup_rotation(
+        &app_data_dir,
+        &app_state.config.builder().settings.backup,
+    )));
+    recycle_bin::purge_expired(
+        &mut vault,
+        app_state.config.builder().settings.recycle_bin_purge_days,
+    )?;
+
+    app_state.vault = Some(vault);
     app_state.is_locked = false;
     app_state.locked_timestamp = None;
     app_state.runtime_timestamp = chrono::Local::now().timestamp() as u64;
+    app_state.touch();
+    app_state.search_index()?;
 
     Ok(())
 }
 
+/// Change the master password, re-encrypting the vault file with the new one.
+/// The vault keeps using its key file, if it has one.
 #[tauri::command]
-pub fn unlock_with_biometric(_app: tauri::AppHandle) {}
+pub fn change_master_key(
+    app: tauri::AppHandle,
+    current_password: SecretString,
+    new_password: SecretString,
+) -> Result<(), CommonError> {
+    utils::validate_password(&new_password)?;
 
-#[tauri::command]
-pub fn lock(app: tauri::AppHandle) -> Result<(), CommonError> {
     let state = app.state::<Arc<Mutex<AppState>>>();
-    let mut state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
-    state.is_locked = true;
-    state.locked_timestamp = Some(chrono::Local::now().timestamp() as u64);
-    state.db = None;
-    state.master_password = None;
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let key_file = app_state.config.builder().key_file().map(Path::to_path_buf);
+    let vault = app_state.vault_mut()?;
+
+    if *vault.key() != database_key(&current_password, key_file.as_deref())? {
+        return Err(CommonError::InvalidPassword);
+    }
+    vault.rekey(database_key(&new_password, key_file.as_deref())?)?;
+
+    info!("master key changed");
+
     Ok(())
 }
 
 #[tauri::command]
-pub fn export_backup(_app: tauri::AppHandle, _password: String) {}
+pub fn unlock_with_biometric(_app: tauri::AppHandle) {}
 
 #[tauri::command]
-pub fn import_backup(_app: tauri::AppHandle, _backup: Vec<u8>, _password: String) {}
+pub fn lock(app: tauri::AppHandle) -> Result<(), CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    auto_lock::lock(&app, &mut state, LockReason::Manual);
+    Ok(())
+}
 
 #[tauri::command]
 pub fn quit_app(app: tauri::AppHandle) {
     app.exit(0);
 }
+
+/// Tells the UI when a save had to merge in changes made to the vault file by another program
+fn notify_external_merges(app: &tauri::AppHandle, vault: &mut VaultStore) {
+    let app = app.clone();
+    vault.on_external_merge(move |report| {
+        info!(
+            "merged external vault changes: {} added, {} updated, {} removed",
+            report.added.len(),
+            report.updated.len(),
+            report.removed.len()
+        );
+        let _ = app.emit(AppEvents::VaultMerged.to_string().as_str(), report);
+    });
+}
@@ -0,0 +1,45 @@
+use crate::commands::accounts::{find_entry, find_entry_mut};
+use crate::commands::audit;
+use crate::state::AppState;
+use crate::utils::recovery_codes;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use xpassword_entity::{AuditAction, RecoveryCode, RecoveryCodeCount, SecretString};
+use xpassword_error::CommonError;
+
+/// Reveal the recovery codes of an account, used ones included.
+/// The access is recorded in the audit trail.
+#[tauri::command]
+pub fn list_recovery_codes(
+    app: tauri::AppHandle,
+    account_id: String,
+) -> Result<Vec<RecoveryCode>, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    let entry = find_entry(&app_state.vault()?.db().root, &account_id)
+        .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+    let codes = recovery_codes::codes(entry);
+    if !codes.is_empty() {
+        audit::record(&app, AuditAction::RevealRecoveryCodes, entry, None)?;
+    }
+
+    Ok(codes)
+}
+
+/// Mark a recovery code as used now. Returns how many codes the account has left.
+#[tauri::command]
+pub fn use_recovery_code(
+    app: tauri::AppHandle,
+    account_id: String,
+    code: SecretString,
+) -> Result<RecoveryCodeCount, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    app_state.vault_mut()?.update(|db| {
+        let entry = find_entry_mut(&mut db.root, &account_id)
+            .ok_or(CommonError::RequestError("Account not found".to_string()))?;
+        recovery_codes::mark_used(entry, &code, chrono::Local::now().timestamp() as u64)
+    })
+}
@@ -0,0 +1,328 @@
+use crate::commands::attachments::release_entry;
+use crate::commands::groups::{find_group, take_node, target_group};
+use crate::state::AppState;
+use keepass::db::{CustomData, CustomDataItem, DeletedObject, Entry, Group, Node, Times, Value};
+use keepass::Database;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use uuid::Uuid;
+use xpassword_entity::{RecycledItem, RecycledKind};
+use xpassword_error::CommonError;
+use xpassword_vault::VaultStore;
+
+/// Custom data key remembering the group a recycled entry or group was deleted from.
+/// KeePass keeps this as `PreviousParentGroup`, which the keepass crate does not read or write.
+const PREVIOUS_PARENT: &str = "XPassword.PreviousParentGroup";
+
+const RECYCLE_BIN_NAME: &str = "Recycle Bin";
+
+/// Built-in KeePass icon of the recycle bin
+const RECYCLE_BIN_ICON: usize = 43;
+
+/// List what is directly in the recycle bin
+#[tauri::command]
+pub async fn list_recycle_bin(app: tauri::AppHandle) -> Result<Vec<RecycledItem>, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let db = app_state.vault()?.db();
+
+    let Some(bin) = recycle_bin(db) else {
+        return Ok(Vec::new());
+    };
+    Ok(bin
+        .children
+        .iter()
+        .map(|node| recycled_item(db, node))
+        .collect())
+}
+
+/// Move an entry or group out of the recycle bin, back into the group it was deleted from.
+/// It goes to the root group if that group no longer exists or was deleted too.
+#[tauri::command]
+pub fn restore_from_recycle_bin(app: tauri::AppHandle, id: String) -> Result<(), CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    app_state.vault_mut()?.update(|db| {
+        let mut node = take_from_bin(db, &id)?;
+        let parent = previous_parent(&node)
+            .filter(|parent| find_group(&db.root, parent).is_some() && !in_recycle_bin(db, parent));
+
+        let (custom_data, times) = node_data_mut(&mut node);
+        custom_data.items.remove(PREVIOUS_PARENT);
+        times.set_location_changed(Times::now());
+
+        target_group(db, parent.as_deref())?.children.push(node);
+        Ok(())
+    })
+}
+
+/// Permanently delete one entry or group from the recycle bin
+#[tauri::command]
+pub fn purge_from_recycle_bin(app: tauri::AppHandle, id: String) -> Result<(), CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    app_state.vault_mut()?.update(|db| {
+        let node = take_from_bin(db, &id)?;
+        destroy(db, node);
+        Ok(())
+    })
+}
+
+/// Permanently delete everything in the recycle bin. Returns the number of items purged.
+#[tauri::command]
+pub fn empty_recycle_bin(app: tauri::AppHandle) -> Result<usize, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+
+    app_state.vault_mut()?.update(|db| {
+        let nodes = match recycle_bin_id(db) {
+            Some(bin) => std::mem::take(&mut target_group(db, Some(&bin))?.children),
+            None => Vec::new(),
+        };
+        let count = nodes.len();
+        for node in nodes {
+            destroy(db, node);
+        }
+        Ok(count)
+    })
+}
+
+/// Deletes the first node matching `is_match`. It goes into the recycle bin when that is
+/// enabled, and is removed for good when it is not or the node is already in the bin.
+/// Returns whether a node was found.
+pub(crate) fn delete_node(
+    db: &mut Database,
+    is_match: &impl Fn(&Node) -> bool,
+) -> Result<bool, CommonError> {
+    let Some(parent) = parent_of(&db.root, is_match) else {
+        return Ok(false);
+    };
+    let from_bin = in_recycle_bin(db, &parent.to_string());
+    let Some(mut node) = take_node(&mut db.root, is_match) else {
+        return Ok(false);
+    };
+    let holds_bin = db
+        .meta
+        .recyclebin_uuid
+        .is_some_and(|bin| node_uuids(&node).contains(&bin));
+
+    if db.meta.recyclebin_enabled.unwrap_or(true) && !from_bin && !holds_bin {
+        let (custom_data, times) = node_data_mut(&mut node);
+        custom_data.items.insert(
+            PREVIOUS_PARENT.to_string(),
+            CustomDataItem {
+                value: Some(Value::Unprotected(parent.to_string())),
+                last_modification_time: Some(Times::now()),
+            },
+        );
+        times.set_location_changed(Times::now());
+
+        let bin = recycle_bin_or_create(db);
+        target_group(db, Some(&bin))?.children.push(node);
+    } else {
+        if holds_bin {
+            db.meta.recyclebin_uuid = None;
+            db.meta.recyclebin_changed = Some(Times::now());
+        }
+        destroy(db, node);
+    }
+    Ok(true)
+}
+
+/// Permanently deletes what has been in the recycle bin for more than `days` days, if set.
+/// Returns the number of items purged.
+pub(crate) fn purge_expired(
+    vault: &mut VaultStore,
+    days: Option<u64>,
+) -> Result<usize, CommonError> {
+    let Some(days) = days else {
+        return Ok(0);
+    };
+    let cutoff = Times::now() - chrono::Duration::days(days as i64);
+    let expired: Vec<String> = recycle_bin(vault.db())
+        .map(|bin| {
+            bin.children
+                .iter()
+                .filter(|node| {
+                    node_times(node)
+                        .get_location_changed()
+                        .is_some_and(|t| *t < cutoff)
+                })
+                .map(node_id)
+                .collect()
+        })
+        .unwrap_or_default();
+    if expired.is_empty() {
+        return Ok(0);
+    }
+
+    vault.update(|db| {
+        for id in &expired {
+            let node = take_from_bin(db, id)?;
+            destroy(db, node);
+        }
+        Ok(expired.len())
+    })
+}
+
+/// Entries outside the recycle bin
+pub(crate) fn active_entries(db: &Database) -> Vec<Entry> {
+    fn collect(group: &Group, bin: Option<Uuid>, result: &mut Vec<Entry>) {
+        for node in &group.children {
+            match node {
+                Node::Group(g) if Some(g.uuid) == bin => {}
+                Node::Group(g) => collect(g, bin, result),
+                Node::Entry(e) => result.push(e.clone()),
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    collect(&db.root, recycle_bin(db).map(|bin| bin.uuid), &mut result);
+    result
+}
+
+/// The recycle bin group, if the database has one
+pub(crate) fn recycle_bin(db: &Database) -> Option<&Group> {
+    find_group(&db.root, &db.meta.recyclebin_uuid?.to_string())
+}
+
+fn recycle_bin_id(db: &Database) -> Option<String> {
+    recycle_bin(db).map(|bin| bin.uuid.to_string())
+}
+
+/// Whether the group `group_id` is the recycle bin or inside it
+fn in_recycle_bin(db: &Database, group_id: &str) -> bool {
+    recycle_bin(db).is_some_and(|bin| find_group(bin, group_id).is_some())
+}
+
+/// Id of the recycle bin group, creating it under the root group the first time
+fn recycle_bin_or_create(db: &mut Database) -> String {
+    if let Some(id) = recycle_bin_id(db) {
+        return id;
+    }
+
+    let mut bin = Group::new(RECYCLE_BIN_NAME);
+    bin.icon_id = Some(RECYCLE_BIN_ICON);
+    bin.enable_autotype = Some("false".to_string());
+    bin.enable_searching = Some("false".to_string());
+    let id = bin.uuid.to_string();
+
+    db.meta.recyclebin_uuid = Some(bin.uuid);
+    db.meta.recyclebin_changed = Some(Times::now());
+    db.root.children.push(Node::Group(bin));
+    id
+}
+
+/// Detaches the node `id` from directly inside the recycle bin
+fn take_from_bin(db: &mut Database, id: &str) -> Result<Node, CommonError> {
+    let not_found = || CommonError::RequestError("Not in the recycle bin".to_string());
+    let bin = recycle_bin_id(db).ok_or_else(not_found)?;
+    let children = &mut target_group(db, Some(&bin))?.children;
+    let index = children
+        .iter()
+        .position(|node| node_id(node) == id)
+        .ok_or_else(not_found)?;
+    Ok(children.remove(index))
+}
+
+/// Removes a detached node for good. It is recorded as deleted so merging with an older copy
+/// of the vault does not bring it back, and files only it used are dropped.
+fn destroy(db: &mut Database, node: Node) {
+    let deletion_time = Times::now();
+    for uuid in node_uuids(&node) {
+        db.deleted_objects.objects.push(DeletedObject {
+            uuid,
+            deletion_time,
+        });
+    }
+    for entry in node_entries(&node) {
+        release_entry(db, entry);
+        if let Some(history) = &entry.history {
+            for version in history.get_entries() {
+                release_entry(db, version);
+            }
+        }
+    }
+}
+
+fn recycled_item(db: &Database, node: &Node) -> RecycledItem {
+    let (name, kind) = match node {
+        Node::Entry(e) => (
+            e.get("Title").unwrap_or_default().to_string(),
+            RecycledKind::Entry,
+        ),
+        Node::Group(g) => (g.name.clone(), RecycledKind::Group),
+    };
+    RecycledItem {
+        id: node_id(node),
+        name,
+        kind,
+        deleted_at: node_times(node)
+            .get_location_changed()
+            .map(|t| t.and_utc().timestamp() as u64),
+        original_group_id: previous_parent(node).filter(|id| *id != db.root.uuid.to_string()),
+    }
+}
+
+fn previous_parent(node: &Node) -> Option<String> {
+    let custom_data = match node {
+        Node::Entry(e) => &e.custom_data,
+        Node::Group(g) => &g.custom_data,
+    };
+    match custom_data.items.get(PREVIOUS_PARENT)?.value.as_ref()? {
+        Value::Unprotected(id) => Some(id.clone()),
+        _ => None,
+    }
+}
+
+/// Uuid of the group directly holding the first node matching `is_match`
+fn parent_of(group: &Group, is_match: &impl Fn(&Node) -> bool) -> Option<Uuid> {
+    if group.children.iter().any(is_match) {
+        return Some(group.uuid);
+    }
+    group.children.iter().find_map(|node| match node {
+        Node::Group(g) => parent_of(g, is_match),
+        Node::Entry(_) => None,
+    })
+}
+
+fn node_id(node: &Node) -> String {
+    match node {
+        Node::Entry(e) => e.uuid.to_string(),
+        Node::Group(g) => g.uuid.to_string(),
+    }
+}
+
+fn node_times(node: &Node) -> &Times {
+    match node {
+        Node::Entry(e) => &e.times,
+        Node::Group(g) => &g.times,
+    }
+}
+
+fn node_data_mut(node: &mut Node) -> (&mut CustomData, &mut Times) {
+    match node {
+        Node::Entry(e) => (&mut e.custom_data, &mut e.times),
+        Node::Group(g) => (&mut g.custom_data, &mut g.times),
+    }
+}
+
+/// Uuids of a node and everything below it
+fn node_uuids(node: &Node) -> Vec<Uuid> {
+    match node {
+        Node::Entry(e) => vec![e.uuid],
+        Node::Group(g) => std::iter::once(g.uuid)
+            .chain(g.children.iter().flat_map(node_uuids))
+            .collect(),
+    }
+}
+
+fn node_entries(node: &Node) -> Vec<&Entry> {
+    match node {
+        Node::Entry(e) => vec![e],
+        Node::Group(g) => g.children.iter().flat_map(node_entries).collect(),
+    }
+}
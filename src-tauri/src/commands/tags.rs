@@ -0,0 +1,126 @@
+use crate::commands::recycle_bin::active_entries;
+use crate::state::AppState;
+use keepass::db::{Entry, Group, Node, Times};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tauri::Manager;
+use uuid::Uuid;
+use xpassword_entity::TagSummary;
+use xpassword_error::CommonError;
+
+/// List every tag in use outside the recycle bin with the number of entries carrying it
+#[tauri::command]
+pub async fn list_tags(app: tauri::AppHandle) -> Result<Vec<TagSummary>, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let db = app_state.vault()?.db();
+
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for entry in active_entries(db) {
+        for tag in &entry.tags {
+            *counts.entry(tag.clone()).or_default() += 1;
+        }
+    }
+
+    Ok(counts
+        .into_iter()
+        .map(|(name, count)| TagSummary { name, count })
+        .collect())
+}
+
+/// Rename a tag on every entry outside the recycle bin. Renaming onto an existing tag
+/// merges the two. Returns the number of entries changed.
+#[tauri::command]
+pub fn rename_tag(
+    app: tauri::AppHandle,
+    tag: String,
+    new_name: String,
+) -> Result<usize, CommonError> {
+    let new_name = normalize_tags(std::slice::from_ref(&new_name));
+    let [new_name] = new_name.as_slice() else {
+        return Err(CommonError::RequestError(
+            "tag name must be a single non-empty tag".to_string(),
+        ));
+    };
+
+    retag(&app, &tag, Some(new_name))
+}
+
+/// Remove a tag from every entry outside the recycle bin.
+/// Returns the number of entries changed.
+#[tauri::command]
+pub fn delete_tag(app: tauri::AppHandle, tag: String) -> Result<usize, CommonError> {
+    retag(&app, &tag, None)
+}
+
+/// Replaces `tag` with `replacement` on every entry, or drops it when there is none
+fn retag(
+    app: &tauri::AppHandle,
+    tag: &str,
+    replacement: Option<&str>,
+) -> Result<usize, CommonError> {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let mut app_state = state.lock().map_err(|_| CommonError::MutexLockFailed)?;
+    let vault = app_state.vault_mut()?;
+
+    if !active_entries(vault.db())
+        .iter()
+        .any(|e| e.tags.iter().any(|t| t == tag))
+    {
+        return Err(CommonError::RequestError("Tag not found".to_string()));
+    }
+
+    vault.update(|db| {
+        let mut changed = 0;
+        let bin = db.meta.recyclebin_uuid;
+        for_each_entry_mut(&mut db.root, bin, &mut |entry| {
+            if !entry.tags.iter().any(|t| t == tag) {
+                return;
+            }
+            let tags: Vec<String> = entry
+                .tags
+                .iter()
+                .map(|t| match replacement {
+                    Some(replacement) if t == tag => replacement.to_string(),
+                    _ => t.clone(),
+                })
+                .filter(|t| replacement.is_some() || t != tag)
+                .collect();
+            set_tags(entry, &tags);
+            changed += 1;
+        });
+        Ok(changed)
+    })
+}
+
+/// Sets the tags of an entry, trimmed and without duplicates
+pub(crate) fn set_tags(entry: &mut Entry, tags: &[String]) {
+    let tags = normalize_tags(tags);
+    if entry.tags != tags {
+        entry.tags = tags;
+        entry.times.set_last_modification(Times::now());
+    }
+}
+
+/// KeePass stores tags as one `;`/`,` separated string, so separators split a tag in two
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    for tag in tags.iter().flat_map(|t| t.split([';', ','])) {
+        let tag = tag.trim();
+        if !tag.is_empty() && !result.iter().any(|t| t == tag) {
+            result.push(tag.to_string());
+        }
+    }
+    result
+}
+
+/// Calls `f` on every entry outside the group `skip`
+fn for_each_entry_mut(group: &mut Group, skip: Option<Uuid>, f: &mut impl FnMut(&mut Entry)) {
+    for node in &mut group.children {
+        match node {
+            Node::Group(g) if Some(g.uuid) == skip => {}
+            Node::Group(g) => for_each_entry_mut(g, skip, f),
+            Node::Entry(e) => f(e),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// Events emitted from the backend to the webviews
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AppEvents {
+    /// A save merged in changes made to the vault file outside the app
+    VaultMerged,
+    /// The vault was locked and its key dropped
+    Locked,
+}
+
+impl Display for AppEvents {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            AppEvents::VaultMerged => write!(f, "vault-merged"),
+            AppEvents::Locked => write!(f, "locked"),
+        }
+    }
+}
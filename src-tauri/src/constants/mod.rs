@@ -1 +1,2 @@
+pub(crate) mod app_events;
 pub(crate) mod webview_window_labels;
@@ -0,0 +1,333 @@
+use crate::importers::{
+    aes_gcm_decrypt, algorithm, checked, display_name, otp_request, otp_type, utf8, ImportBatch,
+    ImportedAccount, Importer,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Deserialize;
+use std::collections::HashMap;
+use xpassword_entity::{
+    normalize_secret, CreateAccountRequest, OtpParams, UnsupportedEntry, DEFAULT_OTP_DIGITS,
+    DEFAULT_OTP_PERIOD,
+};
+use xpassword_error::CommonError;
+
+/// Slot type of a key wrapped with a password-derived scrypt key
+const PASSWORD_SLOT: u8 = 1;
+const MASTER_KEY_LEN: usize = 32;
+/// Upper bounds on the scrypt cost of a slot, so a crafted file cannot make the import
+/// allocate gigabytes or run for minutes. Aegis itself writes n = 2^15, r = 8, p = 1.
+const MAX_SCRYPT_N: u64 = 1 << 20;
+const MAX_SCRYPT_RP: u64 = 16;
+
+#[derive(Deserialize)]
+struct Vault {
+    #[serde(default)]
+    header: Header,
+    db: VaultDb,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum VaultDb {
+    Plain(Database),
+    /// Base64 AES-256-GCM ciphertext of the JSON database
+    Encrypted(String),
+}
+
+#[derive(Deserialize, Default)]
+struct Header {
+    slots: Option<Vec<Slot>>,
+    params: Option<KeyParams>,
+}
+
+#[derive(Deserialize)]
+struct Slot {
+    #[serde(rename = "type")]
+    slot_type: u8,
+    key: String,
+    key_params: KeyParams,
+    n: Option<u64>,
+    r: Option<u32>,
+    p: Option<u32>,
+    salt: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct KeyParams {
+    nonce: String,
+    tag: String,
+}
+
+#[derive(Deserialize)]
+struct Database {
+    entries: Vec<Entry>,
+    /// Group definitions referenced by uuid from entries (database version 3)
+    #[serde(default)]
+    groups: Vec<Group>,
+}
+
+#[derive(Deserialize)]
+struct Group {
+    uuid: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    #[serde(rename = "type")]
+    entry_type: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    issuer: String,
+    #[serde(default)]
+    note: String,
+    icon: Option<String>,
+    info: Info,
+    #[serde(default)]
+    groups: Vec<String>,
+    /// Free-form group name used before database version 3
+    group: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Info {
+    #[serde(default)]
+    secret: String,
+    #[serde(default)]
+    algo: String,
+    digits: Option<u32>,
+    period: Option<u64>,
+    counter: Option<u64>,
+}
+
+/// Aegis Authenticator JSON export
+pub struct Aegis;
+
+impl Importer for Aegis {
+    fn decode(&self, data: &[u8], password: Option<&str>) -> Result<ImportBatch, CommonError> {
+        decode(utf8(data)?, password)
+    }
+}
+
+/// Decodes an Aegis JSON export, decrypting it with `password` when it is encrypted
+pub fn decode(data: &str, password: Option<&str>) -> Result<ImportBatch, CommonError> {
+    let vault: Vault = serde_json::from_str(data)
+        .map_err(|e| CommonError::RequestError(format!("not an Aegis export: {}", e)))?;
+
+    let database = match vault.db {
+        VaultDb::Plain(database) => database,
+        VaultDb::Encrypted(db) => {
+            let password = password.ok_or(CommonError::InvalidPassword)?;
+            let (slots, params) = match (vault.header.slots, vault.header.params) {
+                (Some(slots), Some(params)) => (slots, params),
+                _ => {
+                    return Err(CommonError::RequestError(
+                        "encrypted Aegis export is missing its header".to_string(),
+                    ))
+                }
+            };
+
+            let key = master_key(&slots, password)?;
+            let ciphertext = BASE64
+                .decode(db.trim())
+                .map_err(|e| CommonError::RequestError(e.to_string()))?;
+            let plaintext = decrypt(&ciphertext, &key, &params).ok_or(
+                CommonError::RequestError("failed to decrypt the Aegis database".to_string()),
+            )?;
+            serde_json::from_slice(&plaintext)
+                .map_err(|e| CommonError::RequestError(format!("not an Aegis database: {}", e)))?
+        }
+    };
+
+    let groups: HashMap<&str, &str> = database
+        .groups
+        .iter()
+        .map(|g| (g.uuid.as_str(), g.name.as_str()))
+        .collect();
+
+    let mut batch = ImportBatch::default();
+    for entry in &database.entries {
+        match to_request(entry) {
+            Ok(request) => batch.accounts.push(ImportedAccount {
+                request,
+                group: entry
+                    .groups
+                    .iter()
+                    .find_map(|uuid| groups.get(uuid.as_str()).map(|name| name.to_string()))
+                    .or_else(|| entry.group.clone())
+                    .filter(|name| !name.trim().is_empty()),
+                icon: entry.icon.as_deref().and_then(decode_icon),
+            }),
+            Err(reason) => batch.unsupported.push(UnsupportedEntry {
+                name: display_name(&entry.issuer, &entry.name),
+                reason,
+            }),
+        }
+    }
+
+    Ok(batch)
+}
+
+/// Unwraps the vault master key with the first password slot that accepts `password`
+fn master_key(slots: &[Slot], password: &str) -> Result<Vec<u8>, CommonError> {
+    for slot in slots.iter().filter(|s| s.slot_type == PASSWORD_SLOT) {
+        let (Some(n), Some(r), Some(p), Some(salt)) = (slot.n, slot.r, slot.p, &slot.salt) else {
+            continue;
+        };
+        check_scrypt_params(n, r, p)?;
+
+        let salt = hex::decode(salt).map_err(|e| CommonError::RequestError(e.to_string()))?;
+        let params = scrypt::Params::new(n.trailing_zeros() as u8, r, p, MASTER_KEY_LEN)
+            .map_err(|e| CommonError::RequestError(e.to_string()))?;
+        let mut derived = [0u8; MASTER_KEY_LEN];
+        scrypt::scrypt(password.as_bytes(), &salt, &params, &mut derived)
+            .map_err(|e| CommonError::RequestError(e.to_string()))?;
+
+        let wrapped =
+            hex::decode(&slot.key).map_err(|e| CommonError::RequestError(e.to_string()))?;
+        if let Some(key) = decrypt(&wrapped, &derived, &slot.key_params) {
+            return Ok(key);
+        }
+    }
+
+    Err(CommonError::InvalidPassword)
+}
+
+fn check_scrypt_params(n: u64, r: u32, p: u32) -> Result<(), CommonError> {
+    if !n.is_power_of_two() || n < 2 {
+        return Err(CommonError::RequestError(format!(
+            "Invalid scrypt cost {}: not a power of two",
+            n
+        )));
+    }
+    if n > MAX_SCRYPT_N || r == 0 || p == 0 || r as u64 * p as u64 > MAX_SCRYPT_RP {
+        return Err(CommonError::RequestError(format!(
+            "Unsupported scrypt parameters n={}, r={}, p={}",
+            n, r, p
+        )));
+    }
+    Ok(())
+}
+
+/// AES-256-GCM with the tag stored apart from the ciphertext, as Aegis does
+fn decrypt(ciphertext: &[u8], key: &[u8], params: &KeyParams) -> Option<Vec<u8>> {
+    let nonce = hex::decode(&params.nonce).ok()?;
+    let mut payload = ciphertext.to_vec();
+    payload.extend_from_slice(&hex::decode(&params.tag).ok()?);
+    aes_gcm_decrypt(key, &nonce, &payload)
+}
+
+fn to_request(entry: &Entry) -> Result<CreateAccountRequest, String> {
+    let otp = checked(OtpParams {
+        otp_type: otp_type(&entry.entry_type)?,
+        issuer: Some(entry.issuer.trim().to_string()).filter(|i| !i.is_empty()),
+        label: entry.name.trim().to_string(),
+        secret: normalize_secret(&entry.info.secret),
+        algorithm: algorithm(&entry.info.algo)?,
+        digits: entry.info.digits.unwrap_or(DEFAULT_OTP_DIGITS),
+        period: entry.info.period.unwrap_or(DEFAULT_OTP_PERIOD),
+        counter: entry.info.counter.unwrap_or_default(),
+        image: None,
+    })?;
+
+    Ok(otp_request(&otp, entry.note.clone()))
+}
+
+/// Keeps raster icons only; SVG has no place in the KDBX custom icon list
+fn decode_icon(icon: &str) -> Option<Vec<u8>> {
+    let data = BASE64.decode(icon.trim()).ok()?;
+    let raster = data.starts_with(b"\x89PNG") || data.starts_with(b"\xFF\xD8\xFF");
+    raster.then_some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xpassword_entity::{OtpAlgorithm, OtpType};
+
+    const PLAIN: &str = include_str!("../../tests/fixtures/aegis_plain.json");
+    /// The same database encrypted with the password "hunter2"
+    const ENCRYPTED: &str = include_str!("../../tests/fixtures/aegis_encrypted.json");
+
+    fn otp(batch: &ImportBatch, index: usize) -> OtpParams {
+        batch.accounts[index]
+            .request
+            .totp
+            .as_deref()
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    fn check_accounts(batch: &ImportBatch) {
+        assert_eq!(batch.accounts.len(), 2);
+
+        let acme = &batch.accounts[0];
+        assert_eq!(acme.request.title, "ACME Co");
+        assert_eq!(acme.request.notes, "main");
+        assert_eq!(acme.group.as_deref(), Some("Work"));
+        assert!(acme
+            .icon
+            .as_ref()
+            .is_some_and(|icon| icon.starts_with(b"\x89PNG")));
+        let totp = otp(batch, 0);
+        assert_eq!(totp.otp_type, OtpType::Totp);
+        assert_eq!(totp.label, "john@example.com");
+        assert_eq!(totp.secret, "JBSWY3DPEHPK3PXP");
+
+        let bob = &batch.accounts[1];
+        assert_eq!(bob.group, None);
+        assert_eq!(bob.icon, None);
+        let hotp = otp(batch, 1);
+        assert_eq!(hotp.otp_type, OtpType::Hotp);
+        assert_eq!(hotp.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!(hotp.digits, 8);
+        assert_eq!(hotp.counter, 7);
+
+        assert_eq!(batch.unsupported.len(), 1);
+        assert_eq!(batch.unsupported[0].name, "Legacy (old)");
+    }
+
+    #[test]
+    fn decodes_a_plain_export() {
+        check_accounts(&decode(PLAIN, None).unwrap());
+    }
+
+    #[test]
+    fn decrypts_an_encrypted_export() {
+        check_accounts(&decode(ENCRYPTED, Some("hunter2")).unwrap());
+    }
+
+    #[test]
+    fn rejects_a_wrong_or_missing_password() {
+        assert!(matches!(
+            decode(ENCRYPTED, Some("hunter3")),
+            Err(CommonError::InvalidPassword)
+        ));
+        assert!(matches!(
+            decode(ENCRYPTED, None),
+            Err(CommonError::InvalidPassword)
+        ));
+    }
+
+    #[test]
+    fn rejects_costly_or_malformed_scrypt_parameters() {
+        for (n, r, p) in [
+            (1000, 8, 1),
+            (1 << 21, 8, 1),
+            (1 << 15, 16, 2),
+            (1 << 15, 0, 1),
+        ] {
+            assert!(check_scrypt_params(n, r, p).is_err(), "{} {} {}", n, r, p);
+        }
+        assert!(check_scrypt_params(1 << 15, 8, 1).is_ok());
+
+        let costly = ENCRYPTED.replace("\"n\": 1024", "\"n\": 1073741824");
+        assert!(matches!(
+            decode(&costly, Some("hunter2")),
+            Err(CommonError::RequestError(_))
+        ));
+    }
+}
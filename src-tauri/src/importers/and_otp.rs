@@ -0,0 +1,187 @@
+use crate::importers::{
+    aes_gcm_decrypt, algorithm, checked, display_name, otp_request, otp_type, ImportBatch,
+    ImportedAccount, Importer,
+};
+use serde::Deserialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use xpassword_entity::{
+    normalize_secret, CreateAccountRequest, OtpParams, UnsupportedEntry, DEFAULT_OTP_DIGITS,
+    DEFAULT_OTP_PERIOD,
+};
+use xpassword_error::CommonError;
+
+const ITERATIONS_LEN: usize = 4;
+const SALT_LEN: usize = 12;
+const NONCE_LEN: usize = 12;
+/// Upper bound on the PBKDF2 iterations of a backup; andOTP writes between 140000 and 160000
+const MAX_ITERATIONS: u32 = 5_000_000;
+
+/// andOTP JSON backup, plain or `.json.aes` encrypted
+pub struct AndOtp;
+
+impl Importer for AndOtp {
+    fn decode(&self, data: &[u8], password: Option<&str>) -> Result<ImportBatch, CommonError> {
+        decode(data, password)
+    }
+}
+
+#[derive(Deserialize)]
+struct Entry {
+    #[serde(default)]
+    secret: String,
+    #[serde(default)]
+    issuer: String,
+    #[serde(default)]
+    label: String,
+    digits: Option<u32>,
+    #[serde(rename = "type")]
+    entry_type: String,
+    #[serde(default)]
+    algorithm: String,
+    period: Option<u64>,
+    counter: Option<u64>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// Decodes an andOTP backup, decrypting it with `password` unless it is plain JSON
+pub fn decode(data: &[u8], password: Option<&str>) -> Result<ImportBatch, CommonError> {
+    let plain = data.trim_ascii_start().starts_with(b"[");
+    let entries: Vec<Entry> = if plain {
+        serde_json::from_slice(data)
+    } else {
+        let password = password.ok_or(CommonError::InvalidPassword)?;
+        serde_json::from_slice(&decrypt(data, password)?)
+    }
+    .map_err(|e| CommonError::RequestError(format!("not an andOTP backup: {}", e)))?;
+
+    let mut batch = ImportBatch::default();
+    for entry in &entries {
+        match to_request(entry) {
+            // andOTP has no folders, so its first tag is the closest thing to a group
+            Ok(request) => batch.accounts.push(ImportedAccount {
+                request: CreateAccountRequest {
+                    tags: entry.tags.clone(),
+                    ..request
+                },
+                group: entry.tags.first().cloned(),
+                icon: None,
+            }),
+            Err(reason) => batch.unsupported.push(UnsupportedEntry {
+                name: display_name(&entry.issuer, &entry.label),
+                reason,
+            }),
+        }
+    }
+
+    Ok(batch)
+}
+
+/// Tries the PBKDF2 layout (iterations, salt, nonce, ciphertext) used since andOTP 0.6.3,
+/// then the older one keyed with a bare SHA-256 of the password
+fn decrypt(data: &[u8], password: &str) -> Result<Vec<u8>, CommonError> {
+    let mut too_costly = None;
+    if data.len() > ITERATIONS_LEN + SALT_LEN + NONCE_LEN {
+        let (iterations, rest) = data.split_at(ITERATIONS_LEN);
+        let (salt, rest) = rest.split_at(SALT_LEN);
+        let (nonce, payload) = rest.split_at(NONCE_LEN);
+        let iterations = u32::from_be_bytes(iterations.try_into().unwrap_or_default());
+
+        if iterations > MAX_ITERATIONS {
+            too_costly = Some(iterations);
+        } else if iterations > 0 {
+            let key = pbkdf2::pbkdf2_hmac_array::<Sha1, 32>(password.as_bytes(), salt, iterations);
+            if let Some(plaintext) = aes_gcm_decrypt(&key, nonce, payload) {
+                return Ok(plaintext);
+            }
+        }
+    }
+
+    if data.len() > NONCE_LEN {
+        let (nonce, payload) = data.split_at(NONCE_LEN);
+        let key = Sha256::digest(password.as_bytes());
+        if let Some(plaintext) = aes_gcm_decrypt(&key, nonce, payload) {
+            return Ok(plaintext);
+        }
+    }
+
+    // The first bytes of an old backup are its nonce, so a huge count cannot be told apart
+    // from a wrong password there
+    match too_costly {
+        Some(iterations) => Err(CommonError::RequestError(format!(
+            "Wrong password, or the PBKDF2 iteration count {} is above the supported {}",
+            iterations, MAX_ITERATIONS
+        ))),
+        None => Err(CommonError::InvalidPassword),
+    }
+}
+
+fn to_request(entry: &Entry) -> Result<CreateAccountRequest, String> {
+    let otp = checked(OtpParams {
+        otp_type: otp_type(&entry.entry_type)?,
+        issuer: Some(entry.issuer.trim().to_string()).filter(|i| !i.is_empty()),
+        label: entry.label.trim().to_string(),
+        secret: normalize_secret(&entry.secret),
+        algorithm: algorithm(&entry.algorithm)?,
+        digits: entry.digits.unwrap_or(DEFAULT_OTP_DIGITS),
+        period: entry.period.unwrap_or(DEFAULT_OTP_PERIOD),
+        counter: entry.counter.unwrap_or_default(),
+        image: None,
+    })?;
+
+    Ok(otp_request(&otp, String::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &[u8] = include_bytes!("../../tests/fixtures/and_otp_plain.json");
+    /// `PLAIN` encrypted with the password "hunter2", in the PBKDF2 layout
+    const PBKDF2: &[u8] = include_bytes!("../../tests/fixtures/and_otp_pbkdf2.json.aes");
+    /// `PLAIN` encrypted with the password "hunter2", in the layout before andOTP 0.6.3
+    const LEGACY: &[u8] = include_bytes!("../../tests/fixtures/and_otp_legacy.json.aes");
+
+    #[test]
+    fn files_accounts_under_their_first_tag() {
+        let batch = decode(PLAIN, None).unwrap();
+        assert_eq!(batch.accounts.len(), 2);
+        assert_eq!(batch.accounts[0].group.as_deref(), Some("Work"));
+        assert_eq!(batch.accounts[0].request.tags, ["Work", "Email"]);
+        assert_eq!(batch.accounts[1].group, None);
+        assert_eq!(batch.unsupported[0].reason, "motp is not supported");
+    }
+
+    #[test]
+    fn decrypts_both_layouts() {
+        for data in [PBKDF2, LEGACY] {
+            let batch = decode(data, Some("hunter2")).unwrap();
+            assert_eq!(batch.accounts.len(), 2);
+            assert_eq!(batch.unsupported.len(), 1);
+        }
+    }
+
+    #[test]
+    fn rejects_a_wrong_password() {
+        assert!(matches!(
+            decode(PBKDF2, Some("hunter3")),
+            Err(CommonError::InvalidPassword)
+        ));
+        // The nonce of an old backup reads as a huge iteration count
+        assert!(matches!(
+            decode(LEGACY, Some("hunter3")),
+            Err(CommonError::RequestError(_))
+        ));
+    }
+
+    #[test]
+    fn refuses_excessive_iteration_counts() {
+        let mut data = PBKDF2.to_vec();
+        data[..ITERATIONS_LEN].copy_from_slice(&(MAX_ITERATIONS + 1).to_be_bytes());
+        assert!(matches!(
+            decode(&data, Some("hunter2")),
+            Err(CommonError::RequestError(_))
+        ));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::importers::{
+    algorithm, checked, display_name, otp_request, otp_type, utf8, ImportBatch, Importer,
+};
+use serde::Deserialize;
+use xpassword_entity::{
+    CreateAccountRequest, OtpParams, UnsupportedEntry, DEFAULT_OTP_DIGITS, DEFAULT_OTP_PERIOD,
+};
+use xpassword_error::CommonError;
+
+/// FreeOTP+ JSON export
+pub struct FreeOtpPlus;
+
+impl Importer for FreeOtpPlus {
+    fn decode(&self, data: &[u8], _password: Option<&str>) -> Result<ImportBatch, CommonError> {
+        decode(utf8(data)?)
+    }
+}
+
+#[derive(Deserialize)]
+struct Export {
+    tokens: Vec<Token>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Token {
+    #[serde(default)]
+    algo: String,
+    counter: Option<u64>,
+    digits: Option<u32>,
+    #[serde(default)]
+    issuer_ext: String,
+    #[serde(default)]
+    label: String,
+    period: Option<u64>,
+    /// Raw secret as Java's signed bytes
+    #[serde(default)]
+    secret: Vec<i8>,
+    #[serde(rename = "type")]
+    token_type: String,
+}
+
+/// Decodes a FreeOTP+ `tokens` export
+pub fn decode(data: &str) -> Result<ImportBatch, CommonError> {
+    let export: Export = serde_json::from_str(data)
+        .map_err(|e| CommonError::RequestError(format!("not a FreeOTP+ export: {}", e)))?;
+
+    let mut batch = ImportBatch::default();
+    for token in &export.tokens {
+        match to_request(token) {
+            Ok(request) => batch.accounts.push(request.into()),
+            Err(reason) => batch.unsupported.push(UnsupportedEntry {
+                name: display_name(&token.issuer_ext, &token.label),
+                reason,
+            }),
+        }
+    }
+
+    Ok(batch)
+}
+
+fn to_request(token: &Token) -> Result<CreateAccountRequest, String> {
+    let secret: Vec<u8> = token.secret.iter().map(|b| *b as u8).collect();
+
+    let otp = checked(OtpParams {
+        otp_type: otp_type(&token.token_type)?,
+        issuer: Some(token.issuer_ext.trim().to_string()).filter(|i| !i.is_empty()),
+        label: token.label.trim().to_string(),
+        secret: base32::encode(base32::Alphabet::Rfc4648 { padding: false }, &secret),
+        algorithm: algorithm(&token.algo)?,
+        digits: token.digits.unwrap_or(DEFAULT_OTP_DIGITS),
+        period: token.period.unwrap_or(DEFAULT_OTP_PERIOD),
+        counter: token.counter.unwrap_or_default(),
+        image: None,
+    })?;
+
+    Ok(otp_request(&otp, String::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xpassword_entity::OtpType;
+
+    const EXPORT: &str = include_str!("../../tests/fixtures/free_otp_plus.json");
+
+    #[test]
+    fn decodes_signed_byte_secrets() {
+        let batch = decode(EXPORT).unwrap();
+        assert_eq!(batch.accounts.len(), 2);
+        assert_eq!(batch.unsupported.len(), 1);
+
+        let otp: OtpParams = batch.accounts[0]
+            .request
+            .totp
+            .as_deref()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(otp.secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(otp.issuer.as_deref(), Some("ACME Co"));
+
+        let hotp: OtpParams = batch.accounts[1]
+            .request
+            .totp
+            .as_deref()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(hotp.otp_type, OtpType::Hotp);
+        assert_eq!(hotp.counter, 7);
+        assert_eq!(hotp.secret, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    }
+}
@@ -0,0 +1,320 @@
+use crate::importers::{display_name, otp_request, utf8, ImportBatch, Importer};
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use base64::Engine;
+use prost::Message;
+use url::Url;
+use xpassword_entity::{
+    CreateAccountRequest, OtpAlgorithm, OtpParams, OtpType, UnsupportedEntry, DEFAULT_OTP_PERIOD,
+};
+use xpassword_error::CommonError;
+
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// `MigrationPayload` from Google Authenticator's "Transfer accounts" QR codes
+#[derive(Clone, PartialEq, Message)]
+struct MigrationPayload {
+    #[prost(message, repeated, tag = "1")]
+    otp_parameters: Vec<MigrationOtpParameters>,
+    #[prost(int32, tag = "2")]
+    version: i32,
+    #[prost(int32, tag = "3")]
+    batch_size: i32,
+    #[prost(int32, tag = "4")]
+    batch_index: i32,
+    #[prost(int32, tag = "5")]
+    batch_id: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct MigrationOtpParameters {
+    #[prost(bytes = "vec", tag = "1")]
+    secret: Vec<u8>,
+    #[prost(string, tag = "2")]
+    name: String,
+    #[prost(string, tag = "3")]
+    issuer: String,
+    #[prost(enumeration = "MigrationAlgorithm", tag = "4")]
+    algorithm: i32,
+    #[prost(enumeration = "MigrationDigitCount", tag = "5")]
+    digits: i32,
+    #[prost(enumeration = "MigrationOtpType", tag = "6")]
+    otp_type: i32,
+    #[prost(int64, tag = "7")]
+    counter: i64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+enum MigrationAlgorithm {
+    Unspecified = 0,
+    Sha1 = 1,
+    Sha256 = 2,
+    Sha512 = 3,
+    Md5 = 4,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+enum MigrationDigitCount {
+    Unspecified = 0,
+    Six = 1,
+    Eight = 2,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+enum MigrationOtpType {
+    Unspecified = 0,
+    Hotp = 1,
+    Totp = 2,
+}
+
+/// Google Authenticator "Transfer accounts" export, one migration URI per line
+pub struct GoogleAuthenticator;
+
+impl Importer for GoogleAuthenticator {
+    fn decode(&self, data: &[u8], _password: Option<&str>) -> Result<ImportBatch, CommonError> {
+        let uris: Vec<String> = utf8(data)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        decode(&uris)
+    }
+}
+
+/// Decodes every `otpauth-migration://offline?data=...` URI of a (possibly multi-QR) export
+pub fn decode(uris: &[String]) -> Result<ImportBatch, CommonError> {
+    let mut payloads = uris
+        .iter()
+        .map(|uri| decode_payload(uri))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    check_batch(&mut payloads)?;
+
+    let mut batch = ImportBatch::default();
+    for params in payloads.into_iter().flat_map(|p| p.otp_parameters) {
+        match to_request(&params) {
+            Ok(request) => batch.accounts.push(request.into()),
+            Err(reason) => batch.unsupported.push(UnsupportedEntry {
+                name: display_name(&params.issuer, &params.name),
+                reason,
+            }),
+        }
+    }
+
+    Ok(batch)
+}
+
+fn decode_payload(uri: &str) -> Result<MigrationPayload, CommonError> {
+    let url = Url::parse(uri.trim())
+        .map_err(|e| CommonError::RequestError(format!("invalid migration uri: {}", e)))?;
+
+    if url.scheme() != "otpauth-migration" || url.host_str() != Some("offline") {
+        return Err(CommonError::RequestError(
+            "not a Google Authenticator migration uri".to_string(),
+        ));
+    }
+
+    let data = url
+        .query_pairs()
+        .find(|(k, _)| k == "data")
+        .map(|(_, v)| v.replace(' ', "+"))
+        .ok_or(CommonError::RequestError(
+            "migration uri is missing its data".to_string(),
+        ))?;
+
+    let bytes = BASE64
+        .decode(data.trim())
+        .map_err(|e| CommonError::RequestError(format!("invalid migration data: {}", e)))?;
+
+    MigrationPayload::decode(bytes.as_slice())
+        .map_err(|e| CommonError::RequestError(format!("invalid migration payload: {}", e)))
+}
+
+/// Makes sure a multi-QR export was scanned completely and only once per code
+fn check_batch(payloads: &mut [MigrationPayload]) -> Result<(), CommonError> {
+    let Some(first) = payloads.first() else {
+        return Err(CommonError::RequestError(
+            "no migration payload given".to_string(),
+        ));
+    };
+    let (batch_id, batch_size) = (first.batch_id, first.batch_size.max(1));
+
+    if payloads
+        .iter()
+        .any(|p| p.batch_id != batch_id || p.batch_size.max(1) != batch_size)
+    {
+        return Err(CommonError::RequestError(
+            "migration codes come from different exports".to_string(),
+        ));
+    }
+
+    if payloads.len() != batch_size as usize {
+        return Err(CommonError::RequestError(format!(
+            "migration export spans {} codes but {} were given",
+            batch_size,
+            payloads.len()
+        )));
+    }
+
+    payloads.sort_by_key(|p| p.batch_index);
+    if payloads.iter().zip(0..).any(|(p, i)| p.batch_index != i) {
+        return Err(CommonError::RequestError(
+            "migration codes repeat or are missing a part of the export".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn to_request(params: &MigrationOtpParameters) -> Result<CreateAccountRequest, String> {
+    let otp_type = match MigrationOtpType::try_from(params.otp_type) {
+        Ok(MigrationOtpType::Totp) => OtpType::Totp,
+        Ok(MigrationOtpType::Hotp) => OtpType::Hotp,
+        _ => return Err("OTP type is unspecified or unknown".to_string()),
+    };
+    let algorithm = match MigrationAlgorithm::try_from(params.algorithm) {
+        Ok(MigrationAlgorithm::Unspecified | MigrationAlgorithm::Sha1) => OtpAlgorithm::Sha1,
+        Ok(MigrationAlgorithm::Sha256) => OtpAlgorithm::Sha256,
+        Ok(MigrationAlgorithm::Sha512) => OtpAlgorithm::Sha512,
+        Ok(MigrationAlgorithm::Md5) => return Err("MD5 is not supported".to_string()),
+        Err(_) => return Err(format!("unknown algorithm {}", params.algorithm)),
+    };
+    let digits = match MigrationDigitCount::try_from(params.digits) {
+        Ok(MigrationDigitCount::Eight) => 8,
+        _ => 6,
+    };
+    if params.secret.is_empty() {
+        return Err("missing secret".to_string());
+    }
+
+    let issuer = Some(params.issuer.trim().to_string()).filter(|i| !i.is_empty());
+    let label = match &issuer {
+        Some(issuer) => params
+            .name
+            .strip_prefix(&format!("{}:", issuer))
+            .unwrap_or(&params.name),
+        None => &params.name,
+    }
+    .trim()
+    .to_string();
+
+    let otp = OtpParams {
+        otp_type,
+        issuer,
+        label,
+        secret: base32::encode(base32::Alphabet::Rfc4648 { padding: false }, &params.secret),
+        algorithm,
+        digits,
+        period: DEFAULT_OTP_PERIOD,
+        counter: params.counter.max(0) as u64,
+        image: None,
+    };
+
+    Ok(otp_request(&otp, String::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two accounts: a TOTP "ACME Co:john@example.com" and an eight-digit SHA-256 HOTP "bob"
+    const EXPORT: &str = "otpauth-migration://offline?data=CjUKCkhlbGxvId6tvu8SGEFDTUUgQ286am9obkBleGFtcGxlLmNvbRoHQUNNRSBDbyABKAEwAgojChQxMjM0NTY3ODkwMTIzNDU2Nzg5MBIDYm9iIAIoAjABOAcQARgBIAAoKg%3D%3D";
+
+    /// One MD5 account, which cannot be imported
+    const MD5_EXPORT: &str =
+        "otpauth-migration://offline?data=ChgKBnNlY3JldBIDbWQ1GgNPbGQgBCgBMAIQARgBIAAoBw%3D%3D";
+
+    /// An export of two QR codes, in order
+    const SPLIT_EXPORT: [&str; 2] = [
+        "otpauth-migration://offline?data=CjUKCkhlbGxvId6tvu8SGEFDTUUgQ286am9obkBleGFtcGxlLmNvbRoHQUNNRSBDbyABKAEwAhABGAIgACgJ",
+        "otpauth-migration://offline?data=CiMKFDEyMzQ1Njc4OTAxMjM0NTY3ODkwEgNib2IgAigCMAE4BxABGAIgASgJ",
+    ];
+
+    fn otp(batch: &ImportBatch, index: usize) -> OtpParams {
+        batch.accounts[index]
+            .request
+            .totp
+            .as_deref()
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    fn uris(uris: &[&str]) -> Vec<String> {
+        uris.iter().map(|uri| uri.to_string()).collect()
+    }
+
+    #[test]
+    fn decodes_a_migration_uri() {
+        let batch = decode(&uris(&[EXPORT])).unwrap();
+        assert_eq!(batch.accounts.len(), 2);
+        assert!(batch.unsupported.is_empty());
+
+        let totp = otp(&batch, 0);
+        assert_eq!(totp.otp_type, OtpType::Totp);
+        assert_eq!(totp.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(totp.label, "john@example.com");
+        assert_eq!(totp.secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(totp.algorithm, OtpAlgorithm::Sha1);
+        assert_eq!(totp.digits, 6);
+        assert_eq!(totp.period, DEFAULT_OTP_PERIOD);
+        assert_eq!(batch.accounts[0].request.title, "ACME Co");
+
+        let hotp = otp(&batch, 1);
+        assert_eq!(hotp.otp_type, OtpType::Hotp);
+        assert_eq!(hotp.issuer, None);
+        assert_eq!(hotp.label, "bob");
+        assert_eq!(hotp.algorithm, OtpAlgorithm::Sha256);
+        assert_eq!(hotp.digits, 8);
+        assert_eq!(hotp.counter, 7);
+    }
+
+    #[test]
+    fn reports_unsupported_accounts() {
+        let batch = decode(&uris(&[MD5_EXPORT])).unwrap();
+        assert!(batch.accounts.is_empty());
+        assert_eq!(batch.unsupported.len(), 1);
+        assert_eq!(batch.unsupported[0].reason, "MD5 is not supported");
+    }
+
+    #[test]
+    fn joins_a_multi_code_export_in_any_order() {
+        let batch = decode(&uris(&[SPLIT_EXPORT[1], SPLIT_EXPORT[0]])).unwrap();
+        assert_eq!(otp(&batch, 0).label, "john@example.com");
+        assert_eq!(otp(&batch, 1).label, "bob");
+    }
+
+    #[test]
+    fn rejects_incomplete_or_mixed_exports() {
+        assert!(decode(&uris(&[SPLIT_EXPORT[0]])).is_err());
+        assert!(decode(&uris(&[SPLIT_EXPORT[0], SPLIT_EXPORT[0]])).is_err());
+        assert!(decode(&uris(&[SPLIT_EXPORT[0], EXPORT])).is_err());
+        assert!(decode(&[]).is_err());
+    }
+
+    #[test]
+    fn rejects_other_uris() {
+        for uri in [
+            "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP",
+            "otpauth-migration://online?data=CgA%3D",
+            "otpauth-migration://offline",
+            "otpauth-migration://offline?data=%%%",
+        ] {
+            assert!(decode(&uris(&[uri])).is_err(), "{}", uri);
+        }
+    }
+
+    #[test]
+    fn decodes_one_uri_per_line() {
+        let data = format!("\n{}\n\n{}\n", SPLIT_EXPORT[0], SPLIT_EXPORT[1]);
+        let batch = GoogleAuthenticator.decode(data.as_bytes(), None).unwrap();
+        assert_eq!(batch.accounts.len(), 2);
+    }
+}
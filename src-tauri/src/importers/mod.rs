@@ -0,0 +1,123 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use xpassword_entity::{
+    CreateAccountRequest, ImportSource, OtpAlgorithm, OtpParams, OtpType, SecretString,
+    UnsupportedEntry, STEAM_OTP_DIGITS,
+};
+use xpassword_error::CommonError;
+
+pub mod aegis;
+pub mod and_otp;
+pub mod free_otp_plus;
+pub mod google_authenticator;
+pub mod two_fas;
+
+/// Decoder for one authenticator app's export format
+pub trait Importer {
+    /// Decodes an export, decrypting it with `password` when the format is encrypted
+    fn decode(&self, data: &[u8], password: Option<&str>) -> Result<ImportBatch, CommonError>;
+}
+
+pub fn importer(source: ImportSource) -> &'static dyn Importer {
+    match source {
+        ImportSource::GoogleAuthenticator => &google_authenticator::GoogleAuthenticator,
+        ImportSource::Aegis => &aegis::Aegis,
+        ImportSource::TwoFas => &two_fas::TwoFas,
+        ImportSource::AndOtp => &and_otp::AndOtp,
+        ImportSource::FreeOtpPlus => &free_otp_plus::FreeOtpPlus,
+    }
+}
+
+/// Accounts decoded from an external authenticator export, before insertion
+#[derive(Debug, Default)]
+pub struct ImportBatch {
+    pub accounts: Vec<ImportedAccount>,
+    pub unsupported: Vec<UnsupportedEntry>,
+}
+
+/// A single decoded account plus the metadata that lives outside its fields
+#[derive(Debug)]
+pub struct ImportedAccount {
+    pub request: CreateAccountRequest,
+    /// Name of the top-level group to file the entry under
+    pub group: Option<String>,
+    /// Raw PNG/JPEG icon, stored as a custom icon of the database
+    pub icon: Option<Vec<u8>>,
+}
+
+impl From<CreateAccountRequest> for ImportedAccount {
+    fn from(request: CreateAccountRequest) -> Self {
+        Self {
+            request,
+            group: None,
+            icon: None,
+        }
+    }
+}
+
+/// Builds the entry request for an OTP-only account, titled after its issuer
+fn otp_request(otp: &OtpParams, notes: String) -> CreateAccountRequest {
+    CreateAccountRequest {
+        title: otp.issuer.clone().unwrap_or_else(|| otp.label.clone()),
+        username: otp.label.clone(),
+        password: SecretString::default(),
+        url: String::new(),
+        notes,
+        notes_protected: false,
+        totp: Some(otp.to_string().into()),
+        tags: Vec::new(),
+        custom_fields: Vec::new(),
+        recovery_codes: Vec::new(),
+        group_id: None,
+    }
+}
+
+/// Maps the token type names shared by most exports
+fn otp_type(name: &str) -> Result<OtpType, String> {
+    match name.to_ascii_lowercase().as_str() {
+        "totp" => Ok(OtpType::Totp),
+        "hotp" => Ok(OtpType::Hotp),
+        "steam" => Ok(OtpType::Steam),
+        other => Err(format!("{} is not supported", other)),
+    }
+}
+
+/// Parses an algorithm name, treating a missing one as SHA1
+fn algorithm(name: &str) -> Result<OtpAlgorithm, String> {
+    if name.trim().is_empty() {
+        return Ok(OtpAlgorithm::default());
+    }
+    name.trim().parse().map_err(|e: CommonError| e.to_string())
+}
+
+/// Pins Steam tokens to five characters and validates the result
+fn checked(mut otp: OtpParams) -> Result<OtpParams, String> {
+    if otp.otp_type == OtpType::Steam {
+        otp.digits = STEAM_OTP_DIGITS;
+    }
+    otp.validate().map_err(|e| e.to_string())?;
+    Ok(otp)
+}
+
+fn display_name(issuer: &str, name: &str) -> String {
+    if issuer.is_empty() {
+        name.to_string()
+    } else {
+        format!("{} ({})", issuer, name)
+    }
+}
+
+fn utf8(data: &[u8]) -> Result<&str, CommonError> {
+    std::str::from_utf8(data).map_err(|e| CommonError::RequestError(e.to_string()))
+}
+
+/// AES-256-GCM with a 12-byte nonce and the tag appended to the ciphertext
+fn aes_gcm_decrypt(key: &[u8], nonce: &[u8], payload: &[u8]) -> Option<Vec<u8>> {
+    if nonce.len() != 12 {
+        return None;
+    }
+    Aes256Gcm::new_from_slice(key)
+        .ok()?
+        .decrypt(Nonce::from_slice(nonce), payload)
+        .ok()
+}
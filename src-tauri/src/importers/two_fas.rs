@@ -0,0 +1,188 @@
+use crate::importers::{
+    aes_gcm_decrypt, algorithm, checked, display_name, otp_request, otp_type, utf8, ImportBatch,
+    ImportedAccount, Importer,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use xpassword_entity::{
+    normalize_secret, CreateAccountRequest, OtpParams, UnsupportedEntry, DEFAULT_OTP_DIGITS,
+    DEFAULT_OTP_PERIOD,
+};
+use xpassword_error::CommonError;
+
+const PBKDF2_ITERATIONS: u32 = 10_000;
+
+/// 2FAS `.2fas` backup, optionally password protected
+pub struct TwoFas;
+
+impl Importer for TwoFas {
+    fn decode(&self, data: &[u8], password: Option<&str>) -> Result<ImportBatch, CommonError> {
+        decode(utf8(data)?, password)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Backup {
+    #[serde(default)]
+    services: Vec<Service>,
+    /// `ciphertext:salt:iv`, each part base64, when the backup is encrypted
+    services_encrypted: Option<String>,
+    #[serde(default)]
+    groups: Vec<Group>,
+}
+
+#[derive(Deserialize)]
+struct Group {
+    id: String,
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Service {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    secret: String,
+    #[serde(default)]
+    otp: ServiceOtp,
+    group_id: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct ServiceOtp {
+    account: Option<String>,
+    label: Option<String>,
+    issuer: Option<String>,
+    digits: Option<u32>,
+    period: Option<u64>,
+    #[serde(default)]
+    algorithm: String,
+    counter: Option<u64>,
+    token_type: Option<String>,
+}
+
+/// Decodes a 2FAS backup, decrypting its services with `password` when they are encrypted
+pub fn decode(data: &str, password: Option<&str>) -> Result<ImportBatch, CommonError> {
+    let mut backup: Backup = serde_json::from_str(data)
+        .map_err(|e| CommonError::RequestError(format!("not a 2FAS backup: {}", e)))?;
+
+    if let Some(encrypted) = backup.services_encrypted.as_deref() {
+        let password = password.ok_or(CommonError::InvalidPassword)?;
+        let plaintext = decrypt(encrypted, password)?;
+        backup.services = serde_json::from_slice(&plaintext)
+            .map_err(|e| CommonError::RequestError(format!("not a 2FAS backup: {}", e)))?;
+    }
+
+    let groups: HashMap<&str, &str> = backup
+        .groups
+        .iter()
+        .map(|g| (g.id.as_str(), g.name.as_str()))
+        .collect();
+
+    let mut batch = ImportBatch::default();
+    for service in &backup.services {
+        match to_request(service) {
+            Ok(request) => batch.accounts.push(ImportedAccount {
+                request,
+                group: service
+                    .group_id
+                    .as_deref()
+                    .and_then(|id| groups.get(id))
+                    .map(|name| name.to_string()),
+                icon: None,
+            }),
+            Err(reason) => batch.unsupported.push(UnsupportedEntry {
+                name: display_name(&service.name, service.otp.account.as_deref().unwrap_or("")),
+                reason,
+            }),
+        }
+    }
+
+    Ok(batch)
+}
+
+fn decrypt(encrypted: &str, password: &str) -> Result<Vec<u8>, CommonError> {
+    let parts = encrypted
+        .split(':')
+        .map(|part| BASE64.decode(part.trim()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| CommonError::RequestError(e.to_string()))?;
+    let [payload, salt, iv] = parts.as_slice() else {
+        return Err(CommonError::RequestError(
+            "malformed encrypted 2FAS services".to_string(),
+        ));
+    };
+
+    let key = pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(password.as_bytes(), salt, PBKDF2_ITERATIONS);
+    aes_gcm_decrypt(&key, iv, payload).ok_or(CommonError::InvalidPassword)
+}
+
+fn to_request(service: &Service) -> Result<CreateAccountRequest, String> {
+    let otp = &service.otp;
+    let issuer = otp
+        .issuer
+        .as_deref()
+        .map(str::trim)
+        .filter(|i| !i.is_empty())
+        .unwrap_or(service.name.trim());
+    let label = otp
+        .account
+        .as_deref()
+        .or(otp.label.as_deref())
+        .unwrap_or_default()
+        .trim();
+
+    let otp = checked(OtpParams {
+        otp_type: otp_type(otp.token_type.as_deref().unwrap_or("totp"))?,
+        issuer: Some(issuer.to_string()).filter(|i| !i.is_empty()),
+        label: label.to_string(),
+        secret: normalize_secret(&service.secret),
+        algorithm: algorithm(&otp.algorithm)?,
+        digits: otp.digits.unwrap_or(DEFAULT_OTP_DIGITS),
+        period: otp.period.unwrap_or(DEFAULT_OTP_PERIOD),
+        counter: otp.counter.unwrap_or_default(),
+        image: None,
+    })?;
+
+    Ok(otp_request(&otp, String::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &str = include_str!("../../tests/fixtures/two_fas_plain.2fas");
+    /// `PLAIN` with its services encrypted under the password "hunter2"
+    const ENCRYPTED: &str = include_str!("../../tests/fixtures/two_fas_encrypted.2fas");
+
+    #[test]
+    fn decodes_plain_and_encrypted_services() {
+        for batch in [
+            decode(PLAIN, None).unwrap(),
+            decode(ENCRYPTED, Some("hunter2")).unwrap(),
+        ] {
+            assert_eq!(batch.accounts.len(), 2);
+            assert_eq!(batch.accounts[0].request.title, "ACME Co");
+            assert_eq!(batch.accounts[0].request.username, "john@example.com");
+            assert_eq!(batch.accounts[0].group.as_deref(), Some("Work"));
+            assert_eq!(batch.accounts[1].group, None);
+            assert_eq!(batch.unsupported[0].name, "Legacy (old)");
+        }
+    }
+
+    #[test]
+    fn rejects_a_wrong_or_missing_password() {
+        for password in [Some("hunter3"), None] {
+            assert!(matches!(
+                decode(ENCRYPTED, password),
+                Err(CommonError::InvalidPassword)
+            ));
+        }
+    }
+}
@@ -1,5 +1,6 @@
 mod commands;
 mod constants;
+mod importers;
 mod state;
 mod utils;
 
@@ -43,9 +44,14 @@ pub fn run() {
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_persisted_scope::init())
         .manage(Arc::new(Mutex::new(AppState::default())))
-        .setup(|_app| Ok(()))
+        .setup(|app| {
+            state::auto_lock::spawn(app.handle().clone());
+            Ok(())
+        })
         .on_window_event(|window, event| match event {
-            tauri::WindowEvent::Resized { .. } => {}
+            tauri::WindowEvent::Resized { .. } if window.is_minimized().unwrap_or(false) => {
+                state::auto_lock::on_minimized(window.app_handle())
+            }
             tauri::WindowEvent::CloseRequested { api, .. } => {
                 api.prevent_close();
                 if window.is_visible().unwrap() {
@@ -58,25 +64,63 @@ pub fn run() {
             }
             _ => {}
         })
-        .invoke_handler(tauri::generate_handler![
-            commands::app_default,
-            commands::init_app,
-            commands::launch_app,
-            commands::app_state,
-            commands::unlock_with_password,
-            commands::unlock_with_biometric,
-            commands::lock,
-            commands::accounts::list_groups,
-            commands::accounts::list_tags,
-            commands::accounts::list_accounts,
-            commands::accounts::create_account,
-            commands::accounts::update_account,
-            commands::accounts::delete_account,
-            commands::export_backup,
-            commands::import_backup,
-            commands::accounts::get_code,
-            commands::quit_app,
-        ])
+        .invoke_handler({
+            let handler = tauri::generate_handler![
+                commands::app_default,
+                commands::init_app,
+                commands::launch_app,
+                commands::app_state,
+                commands::unlock_with_password,
+                commands::change_master_key,
+                commands::unlock_with_biometric,
+                commands::lock,
+                commands::groups::list_groups,
+                commands::tags::list_tags,
+                commands::tags::rename_tag,
+                commands::tags::delete_tag,
+                commands::accounts::list_accounts,
+                commands::accounts::search_accounts,
+                commands::accounts::reveal_password,
+                commands::accounts::copy_password,
+                commands::accounts::reveal_field,
+                commands::attachments::list_attachments,
+                commands::attachments::attach_file,
+                commands::attachments::export_attachment,
+                commands::attachments::delete_attachment,
+                commands::recovery_codes::list_recovery_codes,
+                commands::recovery_codes::use_recovery_code,
+                commands::health::health_report,
+                commands::audit::list_audit_events,
+                commands::accounts::create_account,
+                commands::accounts::update_account,
+                commands::accounts::delete_account,
+                commands::history::list_versions,
+                commands::history::diff_versions,
+                commands::history::restore_version,
+                commands::groups::create_group,
+                commands::groups::rename_group,
+                commands::groups::move_group,
+                commands::groups::delete_group,
+                commands::groups::move_account,
+                commands::recycle_bin::list_recycle_bin,
+                commands::recycle_bin::restore_from_recycle_bin,
+                commands::recycle_bin::purge_from_recycle_bin,
+                commands::recycle_bin::empty_recycle_bin,
+                commands::backup::export_backup,
+                commands::backup::import_backup,
+                commands::backup::list_local_backups,
+                commands::backup::restore_local_backup,
+                commands::import::import_accounts,
+                commands::import::preview_import,
+                commands::accounts::get_code,
+                commands::accounts::resync_hotp,
+                commands::quit_app,
+            ];
+            move |invoke| {
+                state::auto_lock::on_command(invoke.message.webview().app_handle());
+                handler(invoke)
+            }
+        })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
@@ -0,0 +1,93 @@
+use crate::constants::app_events::AppEvents;
+use crate::state::AppState;
+use log::info;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+use xpassword_entity::LockReason;
+
+/// How often the idle timer checks the vault
+const TICK: Duration = Duration::from_secs(1);
+
+/// Seconds of wall-clock time a single tick may take before the system is taken to have
+/// been asleep. Sleep and wake are not reported portably, but the timer thread does not run
+/// in between. Locking the screen without sleeping leaves no such gap and is not detected.
+const SLEEP_GAP: u64 = 30;
+
+/// Start of the clock behind [`uptime`]
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// Starts the thread that locks the vault once it has been idle for the configured timeout,
+/// or when the system wakes up from sleep
+pub fn spawn(app: tauri::AppHandle) {
+    thread::spawn(move || {
+        loop {
+            // Only the sleep itself is timed, so waiting for the state lock cannot look like one
+            let before = wall_clock();
+            thread::sleep(TICK);
+            let slept = wall_clock().saturating_sub(before) > SLEEP_GAP;
+
+            let state = app.state::<Arc<Mutex<AppState>>>();
+            let Ok(mut app_state) = state.lock() else {
+                continue;
+            };
+            if app_state.is_locked || app_state.vault.is_none() {
+                continue;
+            }
+
+            let settings = &app_state.config.builder().settings;
+            let idle = uptime().saturating_sub(app_state.last_activity());
+            let reason = if slept && settings.auto_lock_on.sleep {
+                Some(LockReason::Sleep)
+            } else if settings.auto_lock && idle >= settings.auto_lock_timeout {
+                Some(LockReason::Idle)
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                lock(&app, &mut app_state, reason);
+            }
+        }
+    });
+}
+
+/// Resets the idle timer for a command from the webview, so commands that do not open the
+/// vault, such as reading the app state or the audit trail, also count as activity
+pub fn on_command(app: &tauri::AppHandle) {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let Ok(app_state) = state.lock() else {
+        return;
+    };
+    app_state.touch();
+}
+
+/// Locks the vault when a window is minimised, if configured to
+pub fn on_minimized(app: &tauri::AppHandle) {
+    let state = app.state::<Arc<Mutex<AppState>>>();
+    let Ok(mut app_state) = state.lock() else {
+        return;
+    };
+    if app_state.config.builder().settings.auto_lock_on.minimize
+        && !app_state.is_locked
+        && app_state.vault.is_some()
+    {
+        lock(app, &mut app_state, LockReason::Minimized);
+    }
+}
+
+/// Locks the vault and tells every window, so they all return to the lock screen
+pub fn lock(app: &tauri::AppHandle, app_state: &mut AppState, reason: LockReason) {
+    app_state.lock();
+    info!("vault locked: {:?}", reason);
+    let _ = app.emit(AppEvents::Locked.to_string().as_str(), reason);
+}
+
+/// Seconds on a monotonic clock, which changes to the system time cannot move
+pub(crate) fn uptime() -> u64 {
+    START.get_or_init(Instant::now).elapsed().as_secs()
+}
+
+fn wall_clock() -> u64 {
+    chrono::Local::now().timestamp() as u64
+}
@@ -1,7 +1,13 @@
-use keepass::Database;
+use crate::utils::search::SearchIndex;
 use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use xpassword_error::CommonError;
+use xpassword_vault::VaultStore;
 
-#[derive(Default, Serialize, Clone)]
+pub mod auto_lock;
+
+#[derive(Default, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AppState {
     pub is_initialized: bool,
@@ -11,10 +17,74 @@ pub struct AppState {
     pub config: xpassword_config::Config,
 
     #[serde(skip)]
-    pub db: Option<Database>,
-    
+    pub vault: Option<VaultStore>,
+    #[serde(skip)]
+    pub search_index: Option<SearchIndex>,
+    /// When the vault was last used, in [`auto_lock::uptime`] seconds, shared so reads
+    /// through `&self` can record it
     #[serde(skip)]
-    pub master_password: Option<String>,
+    last_activity: Arc<AtomicU64>,
 }
 
-impl AppState {}
+impl AppState {
+    /// The unlocked vault, or the reason it cannot be used. Using it counts as activity.
+    pub fn vault(&self) -> Result<&VaultStore, CommonError> {
+        if self.is_locked {
+            return Err(CommonError::AppIsLocked);
+        }
+        let vault = self.vault.as_ref().ok_or(CommonError::KdbxNotInitialized)?;
+        self.touch();
+        Ok(vault)
+    }
+
+    /// Search index for the unlocked vault, rebuilt when the vault has changed since it was built
+    pub fn search_index(&mut self) -> Result<&SearchIndex, CommonError> {
+        let revision = self.vault()?.revision();
+        let index = match self.search_index.take() {
+            Some(index) if index.revision() == revision => index,
+            _ => SearchIndex::build(self.vault()?.db(), revision),
+        };
+        Ok(self.search_index.insert(index))
+    }
+
+    pub fn vault_mut(&mut self) -> Result<&mut VaultStore, CommonError> {
+        if self.is_locked {
+            return Err(CommonError::AppIsLocked);
+        }
+        self.touch();
+        self.vault.as_mut().ok_or(CommonError::KdbxNotInitialized)
+    }
+
+    /// When the vault was last used, in [`auto_lock::uptime`] seconds
+    pub fn last_activity(&self) -> u64 {
+        self.last_activity.load(Ordering::Relaxed)
+    }
+
+    /// Records that the vault is being used now
+    pub fn touch(&self) {
+        self.last_activity
+            .store(auto_lock::uptime(), Ordering::Relaxed);
+    }
+
+    /// The state as reported to the webview. The vault is left out, so its key and contents
+    /// are never copied.
+    pub fn snapshot(&self) -> AppState {
+        AppState {
+            is_initialized: self.is_initialized,
+            runtime_timestamp: self.runtime_timestamp,
+            is_locked: self.is_locked,
+            locked_timestamp: self.locked_timestamp,
+            config: self.config.clone(),
+            ..AppState::default()
+        }
+    }
+
+    /// Locks the app, dropping the open database along with its key.
+    /// Both wipe their memory on drop: the key and the protected fields of every entry.
+    pub fn lock(&mut self) {
+        self.is_locked = true;
+        self.locked_timestamp = Some(chrono::Local::now().timestamp() as u64);
+        self.vault = None;
+        self.search_index = None;
+    }
+}
@@ -20,4 +20,10 @@ impl AppDataDir {
     pub fn accounts(&self) -> PathBuf {
         self.app_data_dir.join("accounts.kdbx")
     }
+    pub fn backups(&self) -> PathBuf {
+        self.app_data_dir.join("backups")
+    }
+    pub fn audit_log(&self) -> PathBuf {
+        self.app_data_dir.join("audit.log")
+    }
 }
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use xpassword_entity::AuditAction;
+use xpassword_error::CommonError;
+
+/// Size past which the log is moved to `<name>.1`, replacing the previous one there
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// One line of the audit log. Titles are left out so the log tells nothing the vault
+/// keeps encrypted; they are looked up in the vault when the log is read.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    pub timestamp: u64,
+    pub action: AuditAction,
+    pub account_id: String,
+    #[serde(default)]
+    pub field: Option<String>,
+}
+
+/// Appends a record to the audit log, one JSON object per line
+pub fn record(path: &Path, record: &AuditRecord) -> Result<(), CommonError> {
+    let mut line = serde_json::to_string(record).map_err(|e| {
+        CommonError::UnexpectedError(anyhow::anyhow!("Failed to serialize audit event: {}", e))
+    })?;
+    line.push('\n');
+
+    if fs::metadata(path).is_ok_and(|m| m.len() >= MAX_LOG_BYTES) {
+        fs::rename(path, rotated(path)).map_err(|e| {
+            CommonError::UnexpectedError(anyhow::anyhow!("Failed to rotate audit log: {}", e))
+        })?;
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|e| {
+            CommonError::UnexpectedError(anyhow::anyhow!("Failed to write audit log: {}", e))
+        })
+}
+
+/// The most recent `limit` records, newest first, reaching into the rotated log when
+/// needed. Lines that fail to parse are skipped.
+pub fn read(path: &Path, limit: usize) -> Result<Vec<AuditRecord>, CommonError> {
+    let mut records = Vec::new();
+    for path in [path.to_path_buf(), rotated(path)] {
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                return Err(CommonError::UnexpectedError(anyhow::anyhow!(
+                    "Failed to read audit log: {}",
+                    e
+                )))
+            }
+        };
+        records.extend(
+            data.lines()
+                .rev()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .take(limit - records.len()),
+        );
+        if records.len() == limit {
+            break;
+        }
+    }
+    Ok(records)
+}
+
+fn rotated(path: &Path) -> PathBuf {
+    let mut rotated = path.to_path_buf().into_os_string();
+    rotated.push(".1");
+    PathBuf::from(rotated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record_at(path: &Path, timestamp: u64) {
+        let event = AuditRecord {
+            timestamp,
+            action: AuditAction::CopyPassword,
+            account_id: "9c5a3e4f-3a5b-4a7e-8d55-4d1c2a0f6b11".to_string(),
+            field: None,
+        };
+        record(path, &event).unwrap();
+    }
+
+    #[test]
+    fn rotates_a_full_log_and_reads_across_both_files() {
+        let path = std::env::temp_dir().join(format!("xpassword-audit-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(rotated(&path));
+
+        record_at(&path, 1);
+        let full = fs::read(&path)
+            .unwrap()
+            .repeat((MAX_LOG_BYTES as usize) / 64);
+        fs::write(&path, full).unwrap();
+        record_at(&path, 2);
+        record_at(&path, 3);
+
+        assert!(fs::metadata(&path).unwrap().len() < MAX_LOG_BYTES);
+        let timestamps: Vec<u64> = read(&path, 4)
+            .unwrap()
+            .iter()
+            .map(|r| r.timestamp)
+            .collect();
+        assert_eq!(timestamps, [3, 2, 1, 1]);
+
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(rotated(&path));
+    }
+
+    #[test]
+    fn leaves_titles_out_of_the_log() {
+        let line = serde_json::to_string(&AuditRecord {
+            timestamp: 1,
+            action: AuditAction::RevealField,
+            account_id: "id".to_string(),
+            field: Some("PIN".to_string()),
+        })
+        .unwrap();
+        assert_eq!(
+            line,
+            r#"{"timestamp":1,"action":"revealField","accountId":"id","field":"PIN"}"#
+        );
+    }
+}
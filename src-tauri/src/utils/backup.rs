@@ -0,0 +1,288 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use xpassword_entity::BackupMetadata;
+use xpassword_error::CommonError;
+
+/// Bumped whenever the header layout or the payload schema changes
+pub const BACKUP_FORMAT_VERSION: u16 = 1;
+
+const MAGIC: &[u8; 4] = b"XPBK";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: u32 = 32;
+const HEADER_LEN: usize = 4 + 2 + 4 * 3 + SALT_LEN + NONCE_LEN;
+
+/// Argon2id cost: 64 MiB, 3 passes, 4 lanes
+const ARGON2_MEM_COST: u32 = 64 * 1024;
+const ARGON2_TIME_COST: u32 = 3;
+const ARGON2_LANES: u32 = 4;
+
+/// Highest Argon2id costs accepted when opening an archive. The header is only authenticated
+/// after the key is derived, so these keep a crafted one from exhausting memory or time.
+const MAX_ARGON2_MEM_COST: u32 = 1024 * 1024;
+const MAX_ARGON2_TIME_COST: u32 = 10;
+const MAX_ARGON2_LANES: u32 = 16;
+
+/// Everything needed to restore a vault: the KDBX file as saved, the app config and a description
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupPayload {
+    pub metadata: BackupMetadata,
+    pub config: xpassword_config::Builder,
+    /// KDBX bytes, still encrypted with the master key
+    #[serde(serialize_with = "to_base64", deserialize_with = "from_base64")]
+    pub kdbx: Vec<u8>,
+}
+
+/// Cleartext archive header, authenticated as associated data:
+/// magic, format version, Argon2id costs, salt and nonce
+struct Header {
+    version: u16,
+    mem_cost: u32,
+    time_cost: u32,
+    lanes: u32,
+    salt: [u8; SALT_LEN],
+    nonce: [u8; NONCE_LEN],
+}
+
+impl Header {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        bytes.extend_from_slice(&self.mem_cost.to_le_bytes());
+        bytes.extend_from_slice(&self.time_cost.to_le_bytes());
+        bytes.extend_from_slice(&self.lanes.to_le_bytes());
+        bytes.extend_from_slice(&self.salt);
+        bytes.extend_from_slice(&self.nonce);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.strip_prefix(MAGIC)?;
+        let u32_at = |i: usize| Some(u32::from_le_bytes(bytes.get(i..i + 4)?.try_into().ok()?));
+
+        Some(Self {
+            version: u16::from_le_bytes(bytes.get(0..2)?.try_into().ok()?),
+            mem_cost: u32_at(2)?,
+            time_cost: u32_at(6)?,
+            lanes: u32_at(10)?,
+            salt: bytes.get(14..14 + SALT_LEN)?.try_into().ok()?,
+            nonce: bytes
+                .get(14 + SALT_LEN..14 + SALT_LEN + NONCE_LEN)?
+                .try_into()
+                .ok()?,
+        })
+    }
+
+    fn derive_key(&self, password: &str) -> Result<Vec<u8>, CommonError> {
+        let config = argon2::Config {
+            variant: argon2::Variant::Argon2id,
+            version: argon2::Version::Version13,
+            mem_cost: self.mem_cost,
+            time_cost: self.time_cost,
+            lanes: self.lanes,
+            hash_length: KEY_LEN,
+            ..Default::default()
+        };
+        argon2::hash_raw(password.as_bytes(), &self.salt, &config).map_err(|e| {
+            CommonError::UnexpectedError(anyhow::anyhow!("Failed to derive backup key: {}", e))
+        })
+    }
+}
+
+/// Hex SHA-256, used to check the KDBX payload survived the round trip
+pub fn checksum(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Serialises and encrypts a backup under `password`
+pub fn seal(payload: &BackupPayload, password: &str) -> Result<Vec<u8>, CommonError> {
+    let mut header = Header {
+        version: BACKUP_FORMAT_VERSION,
+        mem_cost: ARGON2_MEM_COST,
+        time_cost: ARGON2_TIME_COST,
+        lanes: ARGON2_LANES,
+        salt: [0; SALT_LEN],
+        nonce: [0; NONCE_LEN],
+    };
+    getrandom::fill(&mut header.salt)
+        .and_then(|_| getrandom::fill(&mut header.nonce))
+        .map_err(|e| {
+            CommonError::UnexpectedError(anyhow::anyhow!("Failed to get randomness: {}", e))
+        })?;
+
+    let plaintext = serde_json::to_vec(payload).map_err(|e| {
+        CommonError::UnexpectedError(anyhow::anyhow!("Failed to serialize backup: {}", e))
+    })?;
+
+    let key = header.derive_key(password)?;
+    let mut archive = header.to_bytes();
+    let ciphertext = Aes256Gcm::new_from_slice(&key)
+        .map_err(|e| CommonError::UnexpectedError(anyhow::anyhow!("{}", e)))?
+        .encrypt(
+            Nonce::from_slice(&header.nonce),
+            Payload {
+                msg: &plaintext,
+                aad: &archive,
+            },
+        )
+        .map_err(|e| {
+            CommonError::UnexpectedError(anyhow::anyhow!("Failed to encrypt backup: {}", e))
+        })?;
+    archive.extend_from_slice(&ciphertext);
+
+    Ok(archive)
+}
+
+/// Decrypts a backup archive and checks its KDBX payload against the recorded checksum
+pub fn open(archive: &[u8], password: &str) -> Result<BackupPayload, CommonError> {
+    if archive.len() < HEADER_LEN {
+        return Err(CommonError::RequestError(
+            "not a backup archive".to_string(),
+        ));
+    }
+    let (header_bytes, ciphertext) = archive.split_at(HEADER_LEN);
+    let header = Header::from_bytes(header_bytes).ok_or(CommonError::RequestError(
+        "not a backup archive".to_string(),
+    ))?;
+    if header.version > BACKUP_FORMAT_VERSION {
+        return Err(CommonError::RequestError(format!(
+            "backup format version {} is newer than this app supports",
+            header.version
+        )));
+    }
+    if header.mem_cost > MAX_ARGON2_MEM_COST
+        || header.time_cost > MAX_ARGON2_TIME_COST
+        || header.lanes > MAX_ARGON2_LANES
+    {
+        return Err(CommonError::RequestError(
+            "backup key derivation costs are out of range".to_string(),
+        ));
+    }
+
+    let key = header.derive_key(password)?;
+    let plaintext = Aes256Gcm::new_from_slice(&key)
+        .map_err(|e| CommonError::UnexpectedError(anyhow::anyhow!("{}", e)))?
+        .decrypt(
+            Nonce::from_slice(&header.nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header_bytes,
+            },
+        )
+        .map_err(|_| CommonError::InvalidPassword)?;
+
+    let payload: BackupPayload = serde_json::from_slice(&plaintext)
+        .map_err(|e| CommonError::RequestError(format!("malformed backup: {}", e)))?;
+    if checksum(&payload.kdbx) != payload.metadata.checksum {
+        return Err(CommonError::RequestError(
+            "backup checksum does not match its contents".to_string(),
+        ));
+    }
+
+    Ok(payload)
+}
+
+fn to_base64<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&BASE64.encode(bytes))
+}
+
+fn from_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    BASE64.decode(encoded).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::OnceLock;
+
+    const PASSWORD: &str = "correct horse";
+    const MEM_COST_AT: usize = 6;
+    const SALT_AT: usize = 18;
+
+    fn payload() -> BackupPayload {
+        let kdbx = b"not really a kdbx file".to_vec();
+        BackupPayload {
+            metadata: BackupMetadata {
+                format_version: BACKUP_FORMAT_VERSION,
+                app_version: "v1.0.0".to_string(),
+                created_at: 1_700_000_000,
+                entry_count: 3,
+                group_count: 1,
+                checksum: checksum(&kdbx),
+            },
+            config: xpassword_config::Builder::default(),
+            kdbx,
+        }
+    }
+
+    /// Sealing runs the full Argon2id derivation, so the tests share one archive
+    fn archive() -> &'static [u8] {
+        static ARCHIVE: OnceLock<Vec<u8>> = OnceLock::new();
+        ARCHIVE.get_or_init(|| seal(&payload(), PASSWORD).unwrap())
+    }
+
+    #[test]
+    fn opens_what_it_sealed() {
+        let opened = open(archive(), PASSWORD).unwrap();
+        assert_eq!(opened.metadata, payload().metadata);
+        assert_eq!(opened.kdbx, payload().kdbx);
+    }
+
+    #[test]
+    fn rejects_a_wrong_password() {
+        assert!(matches!(
+            open(archive(), "wrong horse"),
+            Err(CommonError::InvalidPassword)
+        ));
+    }
+
+    #[test]
+    fn rejects_a_tampered_header_or_ciphertext() {
+        for at in [
+            MAGIC.len(),
+            SALT_AT,
+            HEADER_LEN - 1,
+            HEADER_LEN,
+            archive().len() - 1,
+        ] {
+            let mut tampered = archive().to_vec();
+            tampered[at] ^= 1;
+            assert!(open(&tampered, PASSWORD).is_err(), "byte {}", at);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        for len in [0, MAGIC.len(), HEADER_LEN - 1] {
+            assert!(matches!(
+                open(&archive()[..len], PASSWORD),
+                Err(CommonError::RequestError(_))
+            ));
+        }
+        assert!(open(&archive()[..archive().len() - 1], PASSWORD).is_err());
+    }
+
+    #[test]
+    fn rejects_excessive_costs_before_deriving_a_key() {
+        for (offset, value) in [
+            (0, u32::MAX),
+            (0, MAX_ARGON2_MEM_COST + 1),
+            (4, MAX_ARGON2_TIME_COST + 1),
+            (8, MAX_ARGON2_LANES + 1),
+        ] {
+            let mut tampered = archive().to_vec();
+            let at = MEM_COST_AT + offset;
+            tampered[at..at + 4].copy_from_slice(&value.to_le_bytes());
+            assert!(matches!(
+                open(&tampered, PASSWORD),
+                Err(CommonError::RequestError(_))
+            ));
+        }
+    }
+}
@@ -1,6 +1,11 @@
 use xpassword_error::CommonError;
 
 pub mod app_data_dir;
+pub mod audit;
+pub mod backup;
+pub mod otp;
+pub mod recovery_codes;
+pub mod search;
 
 /// Validates password strength requirements
 pub fn validate_password(password: &str) -> Result<(), CommonError> {
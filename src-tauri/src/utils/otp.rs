@@ -0,0 +1,202 @@
+use totp_lite::{totp_custom, Sha1, Sha256, Sha512};
+use xpassword_entity::{OtpAlgorithm, OtpCode, OtpParams, OtpType};
+use xpassword_error::CommonError;
+
+/// Entry field holding the next HOTP counter, as used by KeePass/KeePassXC
+pub const HOTP_COUNTER_FIELD: &str = "HmacOtp-Counter";
+
+/// How many counters past the stored one `resync` is allowed to search
+pub const HOTP_RESYNC_WINDOW: u64 = 100;
+
+const STEAM_ALPHABET: &[u8] = b"23456789BCDFGHJKMNPQRTVWXY";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OtpKind {
+    /// RFC 6238 time-based password with its step in seconds
+    Totp { period: u64 },
+    /// RFC 4226 counter-based password with the counter from the URI
+    Hotp { counter: u64 },
+    /// Steam Guard time-based password with its step in seconds
+    Steam { period: u64 },
+}
+
+/// One-time password generator for the TOTP/HOTP value stored on an entry
+#[derive(Clone, Debug)]
+pub struct Otp {
+    secret: Vec<u8>,
+    algorithm: OtpAlgorithm,
+    digits: u32,
+    kind: OtpKind,
+}
+
+impl Otp {
+    /// Parses either a bare base32 secret (TOTP) or an `otpauth://totp|hotp/...` URI
+    pub fn parse(value: &str) -> Result<Self, CommonError> {
+        Self::from_params(&value.parse()?)
+    }
+
+    pub fn from_params(params: &OtpParams) -> Result<Self, CommonError> {
+        params.validate()?;
+
+        Ok(Self {
+            secret: params.secret_bytes()?,
+            algorithm: params.algorithm,
+            digits: params.digits,
+            kind: match params.otp_type {
+                OtpType::Totp => OtpKind::Totp {
+                    period: params.period,
+                },
+                OtpType::Hotp => OtpKind::Hotp {
+                    counter: params.counter,
+                },
+                OtpType::Steam => OtpKind::Steam {
+                    period: params.period,
+                },
+            },
+        })
+    }
+
+    pub fn kind(&self) -> OtpKind {
+        self.kind
+    }
+
+    /// Computes the RFC 4226 value for a single moving factor
+    pub fn hotp(&self, counter: u64) -> String {
+        self.truncate(counter, self.digits)
+    }
+
+    fn truncate(&self, counter: u64, digits: u32) -> String {
+        // A step of 1 turns totp-lite's `time / step` into the raw counter.
+        match self.algorithm {
+            OtpAlgorithm::Sha1 => totp_custom::<Sha1>(1, digits, &self.secret, counter),
+            OtpAlgorithm::Sha256 => totp_custom::<Sha256>(1, digits, &self.secret, counter),
+            OtpAlgorithm::Sha512 => totp_custom::<Sha512>(1, digits, &self.secret, counter),
+        }
+    }
+
+    /// Generates the TOTP code for the time step containing `timestamp` (unix seconds)
+    pub fn totp(&self, period: u64, timestamp: u64) -> OtpCode {
+        OtpCode {
+            code: self.hotp(timestamp / period),
+            otp_type: OtpType::Totp,
+            period: Some(period),
+            remaining: Some(period - timestamp % period),
+            counter: None,
+        }
+    }
+
+    /// Generates the Steam Guard code for the time step containing `timestamp`
+    pub fn steam(&self, period: u64, timestamp: u64) -> OtpCode {
+        // Ten digits keep the whole 31-bit truncated value, which Steam
+        // then spells out in its own alphabet instead of in decimal.
+        let mut value: u64 = self
+            .truncate(timestamp / period, 10)
+            .parse()
+            .unwrap_or_default();
+        let code = (0..self.digits)
+            .map(|_| {
+                let c = STEAM_ALPHABET[(value % STEAM_ALPHABET.len() as u64) as usize] as char;
+                value /= STEAM_ALPHABET.len() as u64;
+                c
+            })
+            .collect();
+
+        OtpCode {
+            code,
+            otp_type: OtpType::Steam,
+            period: Some(period),
+            remaining: Some(period - timestamp % period),
+            counter: None,
+        }
+    }
+
+    /// Finds the counter following two consecutive codes within the look-ahead window.
+    /// Fails when that counter would not fit in a `u64`.
+    pub fn resync(&self, from: u64, first: &str, second: &str) -> Result<Option<u64>, CommonError> {
+        (from..from.saturating_add(HOTP_RESYNC_WINDOW))
+            .find(|&c| {
+                self.hotp(c) == first && c.checked_add(1).is_some_and(|c| self.hotp(c) == second)
+            })
+            .map(|c| c.checked_add(2).ok_or_else(counter_overflow))
+            .transpose()
+    }
+}
+
+/// The error for an HOTP counter that cannot be advanced any further
+pub fn counter_overflow() -> CommonError {
+    CommonError::RequestError("HOTP counter is at its maximum".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA1_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    const SHA256_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZA";
+    const SHA512_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQGEZDGNA";
+
+    fn otp(secret: &str, algorithm: &str) -> Otp {
+        Otp::parse(&format!(
+            "otpauth://totp/rfc?secret={}&algorithm={}&digits=8",
+            secret, algorithm
+        ))
+        .unwrap()
+    }
+
+    /// Test vectors from RFC 6238 appendix B
+    #[test]
+    fn totp_matches_rfc_6238() {
+        let vectors = [
+            (59, "94287082", "46119246", "90693936"),
+            (1111111109, "07081804", "68084774", "25091201"),
+            (1111111111, "14050471", "67062674", "99943326"),
+            (1234567890, "89005924", "91819424", "93441116"),
+            (2000000000, "69279037", "90698825", "38618901"),
+            (20000000000, "65353130", "77737706", "47863826"),
+        ];
+        let sha1 = otp(SHA1_SECRET, "SHA1");
+        let sha256 = otp(SHA256_SECRET, "SHA256");
+        let sha512 = otp(SHA512_SECRET, "SHA512");
+        for (time, expected1, expected256, expected512) in vectors {
+            assert_eq!(sha1.totp(30, time).code, expected1);
+            assert_eq!(sha256.totp(30, time).code, expected256);
+            assert_eq!(sha512.totp(30, time).code, expected512);
+        }
+    }
+
+    /// Test vectors from RFC 4226 appendix D
+    #[test]
+    fn hotp_matches_rfc_4226() {
+        let otp = Otp::parse(&format!(
+            "otpauth://hotp/rfc?secret={}&counter=0",
+            SHA1_SECRET
+        ))
+        .unwrap();
+        let expected = [
+            "755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583",
+            "399871", "520489",
+        ];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(otp.hotp(counter as u64), *code);
+        }
+        assert_eq!(otp.resync(0, "969429", "338314").unwrap(), Some(5));
+    }
+
+    #[test]
+    fn resync_refuses_to_overflow_the_counter() {
+        let otp = Otp::parse(&format!(
+            "otpauth://hotp/rfc?secret={}&counter=0",
+            SHA1_SECRET
+        ))
+        .unwrap();
+        let (first, second) = (otp.hotp(u64::MAX - 1), otp.hotp(u64::MAX));
+        assert!(otp.resync(u64::MAX - 1, &first, &second).is_err());
+    }
+
+    #[test]
+    fn totp_reports_remaining_seconds() {
+        let code = otp(SHA1_SECRET, "SHA1").totp(30, 59);
+        assert_eq!(code.remaining, Some(1));
+        assert_eq!(code.period, Some(30));
+    }
+}
@@ -0,0 +1,126 @@
+use keepass::db::{Entry, Times, Value};
+use xpassword_entity::{RecoveryCode, RecoveryCodeCount, SecretString};
+use xpassword_error::CommonError;
+
+/// Protected entry field holding the account's recovery codes, one per line.
+/// A used code is followed by a tab and the Unix time it was used at.
+pub const RECOVERY_CODES_FIELD: &str = "RecoveryCodes";
+
+/// Accounts with this many unused codes or fewer are reported as running low
+pub const LOW_RECOVERY_CODES: usize = 2;
+
+/// The recovery codes stored on an entry, in the order they were given
+pub fn codes(entry: &Entry) -> Vec<RecoveryCode> {
+    entry
+        .get(RECOVERY_CODES_FIELD)
+        .map(parse)
+        .unwrap_or_default()
+}
+
+/// How many codes an entry has and how many are unused, or `None` if it has none
+pub fn count(entry: &Entry) -> Option<RecoveryCodeCount> {
+    let codes = codes(entry);
+    if codes.is_empty() {
+        return None;
+    }
+    Some(tally(&codes))
+}
+
+/// Replaces the codes of an entry. Codes that were already stored keep their used time,
+/// and an empty list removes the field.
+pub fn replace(entry: &mut Entry, new_codes: &[SecretString]) {
+    let existing = codes(entry);
+    let mut result: Vec<RecoveryCode> = Vec::new();
+    for code in new_codes.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+        if result.iter().any(|c| same_code(&c.code, code)) {
+            continue;
+        }
+        let used_at = existing
+            .iter()
+            .find(|c| same_code(&c.code, code))
+            .and_then(|c| c.used_at);
+        result.push(RecoveryCode {
+            code: code.into(),
+            used_at,
+        });
+    }
+    store(entry, &result);
+}
+
+/// Marks an unused code as used at `used_at` and returns what is left
+pub fn mark_used(
+    entry: &mut Entry,
+    code: &str,
+    used_at: u64,
+) -> Result<RecoveryCodeCount, CommonError> {
+    let mut codes = codes(entry);
+    let Some(found) = codes.iter_mut().find(|c| same_code(&c.code, code)) else {
+        return Err(CommonError::RequestError(
+            "Recovery code not found".to_string(),
+        ));
+    };
+    if found.used_at.is_some() {
+        return Err(CommonError::RequestError(
+            "Recovery code was already used".to_string(),
+        ));
+    }
+    found.used_at = Some(used_at);
+
+    store(entry, &codes);
+    entry.times.set_last_modification(Times::now());
+    Ok(tally(&codes))
+}
+
+fn tally(codes: &[RecoveryCode]) -> RecoveryCodeCount {
+    RecoveryCodeCount {
+        total: codes.len(),
+        remaining: codes.iter().filter(|c| c.used_at.is_none()).count(),
+    }
+}
+
+fn parse(value: &str) -> Vec<RecoveryCode> {
+    value
+        .lines()
+        .filter_map(|line| {
+            let (code, used_at) = match line.split_once('\t') {
+                Some((code, used_at)) => (code, used_at.trim().parse().ok()),
+                None => (line, None),
+            };
+            let code = code.trim();
+            (!code.is_empty()).then(|| RecoveryCode {
+                code: code.into(),
+                used_at,
+            })
+        })
+        .collect()
+}
+
+fn store(entry: &mut Entry, codes: &[RecoveryCode]) {
+    if codes.is_empty() {
+        entry.fields.remove(RECOVERY_CODES_FIELD);
+        return;
+    }
+    let value = codes
+        .iter()
+        .map(|c| match c.used_at {
+            Some(used_at) => format!("{}\t{}", &*c.code, used_at),
+            None => c.code.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    entry.fields.insert(
+        RECOVERY_CODES_FIELD.to_string(),
+        Value::Protected(value.into_bytes().into()),
+    );
+}
+
+/// Codes are compared the way they are typed: ignoring case, spaces and dashes
+fn same_code(a: &str, b: &str) -> bool {
+    let normalize = |code: &str| {
+        code.chars()
+            .filter(|c| !c.is_whitespace() && *c != '-')
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+    normalize(a) == normalize(b)
+}
@@ -0,0 +1,340 @@
+use chrono::NaiveDateTime;
+use keepass::db::{Entry, Group, Node, Times};
+use keepass::Database;
+use std::cmp::Ordering;
+use uuid::Uuid;
+use xpassword_entity::{SearchRequest, SearchSort};
+
+/// Field weights for free-text matches
+const TITLE_WEIGHT: f64 = 3.0;
+const USERNAME_WEIGHT: f64 = 2.0;
+const URL_WEIGHT: f64 = 1.5;
+const NOTES_WEIGHT: f64 = 1.0;
+
+/// What search needs to know about every entry, built once per vault revision.
+/// Passwords are not kept, only whether they are weak.
+#[derive(Clone, Default)]
+pub struct SearchIndex {
+    revision: u64,
+    documents: Vec<Document>,
+}
+
+#[derive(Clone)]
+struct Document {
+    id: String,
+    title: String,
+    username: String,
+    url: String,
+    notes: String,
+    tags: Vec<String>,
+    groups: Vec<String>,
+    has_totp: bool,
+    expired: bool,
+    weak: bool,
+    created: Option<NaiveDateTime>,
+    modified: Option<NaiveDateTime>,
+}
+
+/// One parsed term of a search query
+#[derive(Debug, PartialEq)]
+enum Term {
+    Text(String),
+    Tag(String),
+    Group(String),
+    HasTotp,
+    Expired(bool),
+    Weak(bool),
+}
+
+impl SearchIndex {
+    pub fn build(db: &Database, revision: u64) -> Self {
+        let mut documents = Vec::new();
+        // The recycle bin is left out, as KeePass does by disabling searching on it
+        let bin = db.meta.recyclebin_uuid;
+        index_group(&db.root, bin, &mut Vec::new(), &mut documents);
+        Self {
+            revision,
+            documents,
+        }
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Ids of the entries matching `request`, in order, and how many matched before paging
+    pub fn search(&self, request: &SearchRequest) -> (Vec<String>, usize) {
+        let terms = parse_query(&request.query);
+        let has_text = terms.iter().any(|t| matches!(t, Term::Text(_)));
+
+        let mut hits: Vec<(&Document, f64)> = self
+            .documents
+            .iter()
+            .filter_map(|doc| score(doc, &terms).map(|score| (doc, score)))
+            .collect();
+
+        let sort = request.sort.unwrap_or(if has_text {
+            SearchSort::Relevance
+        } else {
+            SearchSort::Title
+        });
+        hits.sort_by(|(a, a_score), (b, b_score)| {
+            let order = match sort {
+                SearchSort::Relevance => b_score.partial_cmp(a_score).unwrap_or(Ordering::Equal),
+                SearchSort::Title => a.title.cmp(&b.title),
+                SearchSort::Created => a.created.cmp(&b.created),
+                SearchSort::Modified => a.modified.cmp(&b.modified),
+            };
+            let order = if request.descending {
+                order.reverse()
+            } else {
+                order
+            };
+            order.then_with(|| a.title.cmp(&b.title))
+        });
+
+        let total = hits.len();
+        let ids = hits
+            .into_iter()
+            .skip(request.offset)
+            .take(request.limit.unwrap_or(usize::MAX))
+            .map(|(doc, _)| doc.id.clone())
+            .collect();
+        (ids, total)
+    }
+}
+
+fn index_group(
+    group: &Group,
+    bin: Option<Uuid>,
+    path: &mut Vec<String>,
+    documents: &mut Vec<Document>,
+) {
+    for node in &group.children {
+        match node {
+            Node::Group(g) if Some(g.uuid) == bin => {}
+            Node::Group(g) => {
+                path.push(g.name.to_lowercase());
+                index_group(g, bin, path, documents);
+                path.pop();
+            }
+            Node::Entry(e) => documents.push(document(e, path)),
+        }
+    }
+}
+
+fn document(entry: &Entry, groups: &[String]) -> Document {
+    let field = |key: &str| entry.get(key).unwrap_or_default().to_lowercase();
+
+    Document {
+        id: entry.uuid.to_string(),
+        title: field("Title"),
+        username: field("UserName"),
+        url: field("URL"),
+        notes: field("Notes"),
+        tags: entry.tags.iter().map(|t| t.to_lowercase()).collect(),
+        groups: groups.to_vec(),
+        has_totp: entry.get("TOTP").is_some_and(|t| !t.is_empty()),
+        expired: is_expired(entry),
+        weak: has_weak_password(entry),
+        created: entry.times.get_creation().copied(),
+        modified: entry.times.get_last_modification().copied(),
+    }
+}
+
+/// Whether the entry has an expiry time that has passed
+pub(crate) fn is_expired(entry: &Entry) -> bool {
+    entry.times.expires
+        && entry
+            .times
+            .get_expiry()
+            .is_some_and(|expiry| *expiry <= Times::now())
+}
+
+/// Whether the entry has a password that is too short for the kinds of characters it uses
+pub(crate) fn has_weak_password(entry: &Entry) -> bool {
+    let password = entry.get("Password").unwrap_or_default();
+    !password.is_empty() && is_weak_password(password)
+}
+
+/// Site passwords are judged on their own terms rather than by the master password policy:
+/// shorter than 8 characters is weak, and so is shorter than 16 with fewer than three of
+/// lowercase, uppercase, digits and symbols
+fn is_weak_password(password: &str) -> bool {
+    let length = password.chars().count();
+    let classes = [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_numeric()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ]
+    .into_iter()
+    .filter(|&present| present)
+    .count();
+    length < 8 || (length < 16 && classes < 3)
+}
+
+/// Splits a query into terms. Values may be quoted to include spaces, as in `group:"Ops EU"`,
+/// and unknown `key:value` pairs are searched as plain text.
+fn parse_query(query: &str) -> Vec<Term> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in query.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    tokens
+        .into_iter()
+        .map(|token| {
+            let token = token.to_lowercase();
+            let Some((key, value)) = token.split_once(':') else {
+                return Term::Text(token);
+            };
+            match (key, value) {
+                ("tag", tag) if !tag.is_empty() => Term::Tag(tag.to_string()),
+                ("group", group) if !group.is_empty() => Term::Group(group.to_string()),
+                ("has", "totp" | "otp") => Term::HasTotp,
+                ("expired", value) => Term::Expired(flag(value)),
+                ("weak", value) => Term::Weak(flag(value)),
+                _ => Term::Text(token),
+            }
+        })
+        .collect()
+}
+
+/// `expired:` and `weak:` on their own mean true; only an explicit negative turns them off
+fn flag(value: &str) -> bool {
+    !matches!(value, "false" | "no" | "0")
+}
+
+/// Relevance of a document for all terms, or `None` if any term rules it out
+fn score(doc: &Document, terms: &[Term]) -> Option<f64> {
+    let mut total = 0.0;
+    for term in terms {
+        match term {
+            Term::Text(text) => {
+                total += [
+                    (&doc.title, TITLE_WEIGHT),
+                    (&doc.username, USERNAME_WEIGHT),
+                    (&doc.url, URL_WEIGHT),
+                    (&doc.notes, NOTES_WEIGHT),
+                ]
+                .into_iter()
+                .filter_map(|(field, weight)| fuzzy_score(field, text).map(|s| s * weight))
+                .reduce(f64::max)?;
+            }
+            Term::Tag(tag) if !doc.tags.contains(tag) => return None,
+            Term::Group(group) if !doc.groups.contains(group) => return None,
+            Term::HasTotp if !doc.has_totp => return None,
+            Term::Expired(expired) if doc.expired != *expired => return None,
+            Term::Weak(weak) if doc.weak != *weak => return None,
+            _ => {}
+        }
+    }
+    Some(total)
+}
+
+/// How well `needle` matches `haystack`, both lowercase: exact and prefix matches first,
+/// then substrings, then the needle's characters appearing in order, closer together scoring higher
+fn fuzzy_score(haystack: &str, needle: &str) -> Option<f64> {
+    if haystack.is_empty() {
+        return None;
+    }
+    if haystack == needle {
+        return Some(100.0);
+    }
+    if haystack.starts_with(needle) {
+        return Some(80.0);
+    }
+    if let Some(position) = haystack.find(needle) {
+        let at_word_start = !haystack[..position]
+            .chars()
+            .last()
+            .is_some_and(char::is_alphanumeric);
+        return Some(if at_word_start { 60.0 } else { 40.0 });
+    }
+
+    let mut chars = haystack.char_indices();
+    let mut first = None;
+    let mut last = 0;
+    for c in needle.chars() {
+        let (i, _) = chars.find(|(_, h)| *h == c)?;
+        first.get_or_insert(i);
+        last = i;
+    }
+    let span = (last - first.unwrap_or(0) + 1) as f64;
+    Some(20.0 * needle.len() as f64 / span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_filters_and_text() {
+        assert_eq!(
+            parse_query("GitHub tag:Work has:totp expired: weak:no"),
+            [
+                Term::Text("github".to_string()),
+                Term::Tag("work".to_string()),
+                Term::HasTotp,
+                Term::Expired(true),
+                Term::Weak(false),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_values_keep_their_spaces() {
+        assert_eq!(
+            parse_query(r#"group:"Ops EU" "two words""#),
+            [
+                Term::Group("ops eu".to_string()),
+                Term::Text("two words".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_or_empty_filters_are_text() {
+        assert_eq!(
+            parse_query("https://example.com tag: has:sms"),
+            [
+                Term::Text("https://example.com".to_string()),
+                Term::Text("tag:".to_string()),
+                Term::Text("has:sms".to_string()),
+            ]
+        );
+        assert!(parse_query("   ").is_empty());
+    }
+
+    #[test]
+    fn judges_site_passwords_by_length_and_variety() {
+        assert!(is_weak_password("Ab1!xyz"));
+        assert!(is_weak_password("lowercaseonly12"));
+        assert!(!is_weak_password("Tr0ub4dor&3"));
+        assert!(!is_weak_password("correct horse battery staple"));
+    }
+
+    #[test]
+    fn fuzzy_matches_rank_exact_over_prefix_over_scattered() {
+        let exact = fuzzy_score("github", "github").unwrap();
+        let prefix = fuzzy_score("github enterprise", "github").unwrap();
+        let word = fuzzy_score("my github", "github").unwrap();
+        let scattered = fuzzy_score("gitlab hub", "gthb").unwrap();
+        assert!(exact > prefix && prefix > word && word > scattered);
+        assert_eq!(fuzzy_score("gitlab", "github"), None);
+    }
+}
@@ -1,13 +1,32 @@
 import { invoke, type InvokeArgs, type InvokeOptions, } from "@tauri-apps/api/core";
 import type {
     APIError,
-    Account,
+    AccountSummary,
     AppDefault,
     AppStateResponse,
+    AttachmentSummary,
+    AuditEvent,
+    BackupImportMode,
+    BackupImportReport,
     CreateAccountRequest,
+    CreateGroupRequest,
     Entry,
-    Group,
+    EntryVersion,
+    FieldChange,
+    GroupSummary,
+    HealthReport,
+    ImportPreview,
+    ImportReport,
+    ImportSource,
     InitRequest,
+    LocalBackup,
+    OtpCode,
+    RecoveryCode,
+    RecoveryCodeCount,
+    RecycledItem,
+    SearchRequest,
+    SearchResult,
+    TagSummary,
     UpdateAccountRequest,
 } from "$lib/api/types";
 
@@ -18,15 +37,49 @@ export const launchApp = async () => await apiInvoke<void>("launch_app");
 export const appState = async () => await apiInvoke<AppStateResponse>("app_state");
 export const quitApp = async () => await apiInvoke<void>("quit_app");
 export const lockApp = async () => await apiInvoke<void>("lock");
-export const unlockAppWithPassword = async (password: string) => await apiInvoke<void>("unlock_with_password", { password });
+export const unlockAppWithPassword = async (password: string, keyFile?: string) => await apiInvoke<void>("unlock_with_password", { password, keyFile });
+export const changeMasterKey = async (currentPassword: string, newPassword: string) => await apiInvoke<void>("change_master_key", { currentPassword, newPassword });
 
-export const listGroups = async () => await apiInvoke<Group[]>("list_groups");
-export const listTags = async () => await apiInvoke<string[]>("list_tags");
-export const listAccounts = async () => await apiInvoke<Account[]>("list_accounts");
+export const listGroups = async () => await apiInvoke<GroupSummary[]>("list_groups");
+export const listTags = async () => await apiInvoke<TagSummary[]>("list_tags");
+export const renameTag = async (tag: string, newName: string) => await apiInvoke<number>("rename_tag", { tag, newName });
+export const deleteTag = async (tag: string) => await apiInvoke<number>("delete_tag", { tag });
+export const listAccounts = async () => await apiInvoke<AccountSummary[]>("list_accounts");
+export const searchAccounts = async (request: SearchRequest) => await apiInvoke<SearchResult>("search_accounts", { request });
+export const revealPassword = async (accountId: string) => await apiInvoke<string>("reveal_password", { accountId });
+export const copyPassword = async (accountId: string) => await apiInvoke<void>("copy_password", { accountId });
+export const revealField = async (accountId: string, key: string) => await apiInvoke<string>("reveal_field", { accountId, key });
+export const listAttachments = async (accountId: string) => await apiInvoke<AttachmentSummary[]>("list_attachments", { accountId });
+export const attachFile = async (accountId: string, name: string, data: Uint8Array) => await apiInvoke<AttachmentSummary>("attach_file", { accountId, name, data: Array.from(data) });
+export const exportAttachment = async (accountId: string, name: string, path?: string) => await apiInvoke<number[] | null>("export_attachment", { accountId, name, path });
+export const deleteAttachment = async (accountId: string, name: string) => await apiInvoke<void>("delete_attachment", { accountId, name });
+export const listRecoveryCodes = async (accountId: string) => await apiInvoke<RecoveryCode[]>("list_recovery_codes", { accountId });
+export const useRecoveryCode = async (accountId: string, code: string) => await apiInvoke<RecoveryCodeCount>("use_recovery_code", { accountId, code });
+export const healthReport = async () => await apiInvoke<HealthReport>("health_report");
+export const listAuditEvents = async (limit?: number) => await apiInvoke<AuditEvent[]>("list_audit_events", { limit });
 export const createAccount = async (request: CreateAccountRequest) => await apiInvoke<void>("create_account", { request });
 export const updateAccount = async (request: UpdateAccountRequest) => await apiInvoke<void>("update_account", { request });
 export const deleteAccount = async (accountId: string) => await apiInvoke<void>("delete_account", { accountId });
-export const getCode = async (accountId: string) => await apiInvoke<string>("get_code", { accountId });
+export const listVersions = async (accountId: string) => await apiInvoke<EntryVersion[]>("list_versions", { accountId });
+export const diffVersions = async (accountId: string, from?: number, to?: number) => await apiInvoke<FieldChange[]>("diff_versions", { accountId, from, to });
+export const restoreVersion = async (accountId: string, index: number) => await apiInvoke<void>("restore_version", { accountId, index });
+export const createGroup = async (request: CreateGroupRequest) => await apiInvoke<string>("create_group", { request });
+export const renameGroup = async (groupId: string, name: string) => await apiInvoke<void>("rename_group", { groupId, name });
+export const moveGroup = async (groupId: string, parentId?: string, index?: number) => await apiInvoke<void>("move_group", { groupId, parentId, index });
+export const deleteGroup = async (groupId: string, recursive: boolean) => await apiInvoke<void>("delete_group", { groupId, recursive });
+export const moveAccount = async (accountId: string, groupId?: string) => await apiInvoke<void>("move_account", { accountId, groupId });
+export const listRecycleBin = async () => await apiInvoke<RecycledItem[]>("list_recycle_bin");
+export const restoreFromRecycleBin = async (id: string) => await apiInvoke<void>("restore_from_recycle_bin", { id });
+export const purgeFromRecycleBin = async (id: string) => await apiInvoke<void>("purge_from_recycle_bin", { id });
+export const emptyRecycleBin = async () => await apiInvoke<number>("empty_recycle_bin");
+export const getCode = async (accountId: string) => await apiInvoke<OtpCode>("get_code", { accountId });
+export const importAccounts = async (source: ImportSource, data: Uint8Array, password?: string) => await apiInvoke<ImportReport>("import_accounts", { source, data: Array.from(data), password });
+export const previewImport = async (source: ImportSource, data: Uint8Array, password?: string) => await apiInvoke<ImportPreview>("preview_import", { source, data: Array.from(data), password });
+export const resyncHotp = async (accountId: string, firstCode: string, secondCode: string) => await apiInvoke<number>("resync_hotp", { accountId, firstCode, secondCode });
+export const exportBackup = async (password: string, path?: string) => await apiInvoke<number[] | null>("export_backup", { password, path });
+export const importBackup = async (backup: Uint8Array, password: string, mode: BackupImportMode, masterPassword?: string) => await apiInvoke<BackupImportReport>("import_backup", { backup: Array.from(backup), password, mode, masterPassword });
+export const listLocalBackups = async () => await apiInvoke<LocalBackup[]>("list_local_backups");
+export const restoreLocalBackup = async (name: string) => await apiInvoke<void>("restore_local_backup", { name });
 
 async function apiInvoke<T>(
     cmd: string,
@@ -9,4 +9,6 @@ export enum CommonError {
     DatabaseError = "DatabaseError",
     RequestError = "RequestError",
     TokenExpired = "TokenExpired",
+    InvalidOtp = "InvalidOtp",
+    InvalidKeyFile = "InvalidKeyFile",
 }
\ No newline at end of file
@@ -8,6 +8,8 @@ export type AppDefault = {
 export type InitRequest = {
     kdbxPath: string,
     password: string,
+    keyFile?: string,
+    generateKeyFile?: boolean,
 }
 
 export type APIError = {
@@ -28,8 +30,14 @@ export type AppStateResponse = {
                 theme: string,
                 language: string,
                 autoLock: boolean,
-                autoLockTimeout: number
-            }
+                autoLockTimeout: number,
+                autoLockOn: LockTriggers,
+                backup: BackupPolicy,
+                attachments: AttachmentLimits,
+                history: HistoryLimits,
+                recycleBinPurgeDays?: number
+            },
+            keyFiles: Record<string, string>
         }
     }
 }
@@ -132,31 +140,263 @@ export type Entry = {
     history?: History,
 }
 
-export type Account = {
+export type AccountSummary = {
     id: string;
     title: string;
     username: string;
-    password: string;
     url: string;
     notes: string;
-    totp?: string;
+    notesProtected: boolean;
+    hasPassword: boolean;
+    otpType?: OtpType;
+    hotpCounter?: number;
+    tags: string[];
+    customFields: CustomField[];
+    recoveryCodes?: RecoveryCodeCount;
 }
 
+export type CustomField = {
+    key: string;
+    value: string;
+    protected: boolean;
+};
+
 export type CreateAccountRequest = {
     title: string;
     username: string;
     password: string;
     url: string;
     notes: string;
+    notesProtected?: boolean;
     totp?: string;
+    tags?: string[];
+    customFields?: CustomField[];
+    recoveryCodes?: string[];
+    groupId?: string;
 };
 
 export type UpdateAccountRequest = {
     id: string;
     title: string;
     username: string;
-    password: string;
+    password?: string;
     url: string;
     notes: string;
+    notesProtected?: boolean;
     totp?: string;
-};
\ No newline at end of file
+    tags?: string[];
+    customFields?: CustomField[];
+    recoveryCodes?: string[];
+    groupId?: string;
+};
+
+export type RecoveryCode = {
+    code: string;
+    usedAt?: number;
+};
+
+export type RecoveryCodeCount = {
+    total: number;
+    remaining: number;
+};
+
+export type SearchSort = "relevance" | "title" | "created" | "modified";
+
+export type SearchRequest = {
+    query: string;
+    sort?: SearchSort;
+    descending?: boolean;
+    offset?: number;
+    limit?: number;
+};
+
+export type SearchResult = {
+    total: number;
+    accounts: AccountSummary[];
+};
+
+export type TagSummary = {
+    name: string;
+    count: number;
+};
+
+export type GroupSummary = {
+    id: string;
+    name: string;
+    iconId?: number;
+    customIconId?: string;
+    parentId?: string;
+    entryCount: number;
+    totalEntryCount: number;
+    expanded: boolean;
+    recycleBin: boolean;
+    children: GroupSummary[];
+};
+
+export type CreateGroupRequest = {
+    name: string;
+    parentId?: string;
+};
+
+export type OtpType = "totp" | "hotp" | "steam";
+
+export type OtpAlgorithm = "SHA1" | "SHA256" | "SHA512";
+
+export type OtpParams = {
+    otpType: OtpType;
+    issuer?: string;
+    label: string;
+    secret: string;
+    algorithm: OtpAlgorithm;
+    digits: number;
+    period: number;
+    counter: number;
+    image?: string;
+};
+
+export type OtpCode = {
+    code: string;
+    otpType: OtpType;
+    period?: number;
+    remaining?: number;
+    counter?: number;
+};
+
+export type UnsupportedEntry = {
+    name: string;
+    reason: string;
+};
+
+export type ImportSource = "googleAuthenticator" | "aegis" | "twoFas" | "andOtp" | "freeOtpPlus";
+
+export type ImportPreview = {
+    count: number;
+    conflicts: string[];
+    unsupported: UnsupportedEntry[];
+};
+
+export type ImportReport = {
+    imported: number;
+    duplicates: string[];
+    unsupported: UnsupportedEntry[];
+};
+
+export type BackupMetadata = {
+    formatVersion: number;
+    appVersion: string;
+    createdAt: number;
+    entryCount: number;
+    groupCount: number;
+    checksum: string;
+};
+
+export type BackupImportMode = "replace" | "merge" | "preview";
+
+export type BackupImportReport = {
+    metadata: BackupMetadata;
+    added: string[];
+    changed: string[];
+    conflicts: string[];
+};
+
+/** Payload of the `vault-merged` event */
+export type ExternalMergeReport = {
+    added: string[];
+    updated: string[];
+    removed: string[];
+    warnings: string[];
+};
+
+/**
+ * Events that lock the vault besides the idle timeout. Locking the screen is not detected,
+ * so show `settings.screenLockUnsupported` next to these options.
+ */
+export type LockTriggers = {
+    sleep: boolean;
+    minimize: boolean;
+};
+
+/** Payload of the `locked` event */
+export type LockReason = "manual" | "idle" | "sleep" | "minimized";
+
+export type BackupPolicy = {
+    enabled: boolean;
+    keepLast: number;
+    keepDaily: number;
+    keepWeekly: number;
+    dir: string;
+};
+
+export type LocalBackup = {
+    name: string;
+    createdAt: number;
+    size: number;
+};
+
+export type AttachmentLimits = {
+    maxSize: number;
+    maxTotalSize: number;
+};
+
+export type HistoryLimits = {
+    maxItems: number;
+    maxSize: number;
+};
+
+export type AuditAction = "revealPassword" | "copyPassword" | "revealField" | "exportAttachment" | "revealRecoveryCodes";
+
+export type AuditEvent = {
+    timestamp: number;
+    action: AuditAction;
+    accountId: string;
+    title?: string;
+    field?: string;
+};
+
+/**
+ * A file attached to an account. Only XPassword links it to the entry: other KeePass
+ * clients may drop it when they save the vault, so show `attachments.keepassWarning`.
+ */
+export type AttachmentSummary = {
+    name: string;
+    size: number;
+};
+
+export type HealthIssue =
+    | { kind: "weakPassword" }
+    | { kind: "expired" }
+    | { kind: "lowRecoveryCodes"; remaining: number };
+
+export type HealthWarning = {
+    accountId: string;
+    title: string;
+    issue: HealthIssue;
+};
+
+export type HealthReport = {
+    accounts: number;
+    warnings: HealthWarning[];
+};
+
+export type EntryVersion = {
+    index: number;
+    modifiedAt?: number;
+    title: string;
+};
+
+export type FieldChange = {
+    field: string;
+    old?: string;
+    new?: string;
+    protected: boolean;
+};
+
+export type RecycledKind = "entry" | "group";
+
+export type RecycledItem = {
+    id: string;
+    name: string;
+    kind: RecycledKind;
+    deletedAt?: number;
+    originalGroupId?: string;
+};
@@ -0,0 +1,4 @@
+export enum AppEvents {
+    VaultMerged = "vault-merged",
+    Locked = "locked",
+}
@@ -1,12 +1,14 @@
 import { writable } from "svelte/store";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import { AppEvents } from "$lib/constants/app-events";
 import type { AppStateResponse } from "$lib/api/types";
 import { appState } from "$lib/api/api";
-import type { Account } from "$lib/api/types";
+import type { AccountSummary } from "$lib/api/types";
 
 
 export const appStore = writable<AppStateResponse | null>(null);
 export const appIsLocked = writable<boolean>(false);
-export const accountDialogState = writable<{ open: boolean, mode: 'create' | 'edit', account?: Account }>({ open: false, mode: 'create' });
+export const accountDialogState = writable<{ open: boolean, mode: 'create' | 'edit', account?: AccountSummary }>({ open: false, mode: 'create' });
 export const refreshAccountsTrigger = writable<number>(0);
 
 
@@ -14,22 +16,18 @@ export const appStateChange = (v: AppStateResponse) => {
     appStore.set(v);
     appIsLocked.set(v.isLocked ?? false);
 }
-let intervalId: number | undefined;
+let unlistenLocked: UnlistenFn | undefined;
 
+// The state is not polled: every command resets the idle timer, so polling would keep the
+// vault unlocked. Locking is announced with the `locked` event instead.
 export const listenAppStateChange = () => {
-    if (intervalId) {
-        clearInterval(intervalId);
-    }
-
     (async () => {
         appState().then(appStateChange)
-        intervalId = setInterval(() => appState().then(appStateChange), 3000);
+        unlistenLocked ??= await listen(AppEvents.Locked, () => appState().then(appStateChange));
     })()
 }
 
 export const stopListeningAppStateChange = () => {
-    if (intervalId) {
-        clearInterval(intervalId);
-        intervalId = undefined;
-    }
+    unlistenLocked?.();
+    unlistenLocked = undefined;
 }
\ No newline at end of file